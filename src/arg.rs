@@ -1,57 +1,236 @@
-use crate::ArgumentValue;
+use crate::{ArgumentValue, Syntax};
 use std::{
+    borrow::Cow,
     fmt::{Debug, Display},
     marker::PhantomData,
-    num::NonZeroU8,
+    ops::{Bound, RangeBounds},
 };
 
 #[derive(Debug, Default)]
 pub struct Arg<'s, T: ArgumentValue<'s>> {
     pub ctx: ArgContext,
     pub out: T,
+    /// Moved to the option table once added, see [Arg::conflicts_with].
+    pub(crate) relations: Vec<Relation>,
+    /// Shown in the help message, moved to the option table once added, see
+    /// [Arg::with_default].
+    pub(crate) default: Option<Cow<'static, str>>,
+    /// Whether this argument was given, for [Arg::required].
+    pub(crate) seen: bool,
     pub(crate) _phantom: PhantomData<&'s ()>,
 }
 
 #[derive(Default, Clone, Copy)]
 pub struct ArgContext {
-    pub short: Option<NonZeroU8>,
+    pub short: Option<char>,
     pub long: Option<&'static str>,
     pub help: Option<&'static str>,
+    /// Accept values starting with `-`, see [Arg::allow_hyphen_values].
+    pub allow_hyphen_values: bool,
+    /// The long option turning this argument off, see [crate::Arg::negatable].
+    pub negation: Option<Negation>,
+    /// How many values each occurrence takes, see [crate::Arg::with_arity].
+    pub arity: Option<Arity>,
+    /// ASCII character splitting every value into several, see [crate::Arg::with_delimiter].
+    pub delimiter: Option<char>,
+    /// Name of the positional argument, see [crate::Arg::positional].
+    pub positional: Option<&'static str>,
+    /// Set by [crate::ArgumentValue::REQUIRED] or [crate::Arg::required].
+    pub required: bool,
+    /// Set by [crate::ArgumentValue::MULTIPLE].
+    pub multiple: bool,
+    /// Takes no value, set by [crate::ArgumentValue::FLAG].
+    pub flag: bool,
+    /// Also recognised by subcommands, see [crate::Arg::global].
+    pub global: bool,
+    /// Every accepted value, see [crate::ValueEnum].
+    pub possible_values: Option<&'static [crate::PossibleValue]>,
+    /// Read when this argument isn't given, see [crate::Arg::with_env].
+    pub env: Option<&'static str>,
+}
+
+/// How an argument relates to the option with another long name, checked once parsing is done.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Relation {
+    ConflictsWith(&'static str),
+    Requires(&'static str),
+    RequiredUnless(&'static str),
+    Implies(&'static str),
+}
+
+impl Relation {
+    /// Returns the long name of the other option.
+    #[inline]
+    pub(crate) fn long(self) -> &'static str {
+        match self {
+            Relation::ConflictsWith(long)
+            | Relation::Requires(long)
+            | Relation::RequiredUnless(long)
+            | Relation::Implies(long) => long,
+        }
+    }
+}
+
+/// How the option turning off a flag is named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Negation {
+    /// `--no-{long}`
+    Prefixed,
+    /// A custom long option, such as `--quiet` for `--verbose`.
+    Named(&'static str),
 }
 
 impl Debug for ArgContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ArgContext")
-            .field("short", &self.short.map(|v| v.get() as char))
+            .field("short", &self.short)
             .field("long", &self.long)
             .field("help", &self.help)
+            .field("allow_hyphen_values", &self.allow_hyphen_values)
+            .field("negation", &self.negation)
+            .field("arity", &self.arity)
+            .field("delimiter", &self.delimiter)
+            .field("positional", &self.positional)
+            .field("required", &self.required)
+            .field("multiple", &self.multiple)
+            .field("flag", &self.flag)
+            .field("global", &self.global)
+            .field("possible_values", &self.possible_values)
+            .field("env", &self.env)
             .finish()
     }
 }
 
 impl Display for ArgContext {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_with(&Syntax::GNU, f)
+    }
+}
+
+impl ArgContext {
+    /// Write the names of this argument as they're typed with `syntax`, followed by its help.
+    pub(crate) fn write_with(
+        &self,
+        syntax: &Syntax,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
         let help = self.help.unwrap_or("[No help message]");
-        if self.short.is_none() && self.long.is_none() {
+        if self.short.is_none() && self.long.is_none() && self.positional.is_none() {
             f.write_str("No flags set!")?;
         }
-        if let Some(short) = self.short {
-            write!(f, "-{} ", short.get() as char)?;
+        if self.positional.is_some() {
+            self.write_positional(f)?;
+            f.write_str(" ")?;
         }
-        if let Some(long) = self.long {
-            write!(f, "--{long} ")?;
+        if let Some(short) = self.short
+            && let Some(prefix) = syntax.short_option_prefix()
+        {
+            write!(f, "{prefix}{short} ")?;
+        }
+        match (self.long, self.negation) {
+            (Some(long), Some(Negation::Prefixed)) => {
+                write!(f, "{}[no-]{long} ", syntax.long_prefix)?
+            }
+            (Some(long), Some(Negation::Named(negation))) => {
+                syntax.write_long(long, f)?;
+                f.write_str(" ")?;
+                syntax.write_long(negation, f)?;
+                f.write_str(" ")?;
+            }
+            (Some(long), None) => {
+                syntax.write_long(long, f)?;
+                f.write_str(" ")?;
+            }
+            (None, _) => (),
         }
         f.write_str(help)
     }
-}
-
-impl ArgContext {
+    /// Returns the names telling this argument apart from the others of a command.
+    #[inline]
+    pub(crate) fn names(&self) -> (Option<char>, Option<&'static str>, Option<&'static str>) {
+        (self.short, self.long, self.positional)
+    }
+    /// Write the name of a positional argument as shown in the usage line, such as `<input>`,
+    /// `[output]` or `[rest]...`.
+    pub(crate) fn write_positional(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.positional.unwrap_or_default();
+        match self.required {
+            true => write!(f, "<{name}>")?,
+            false => write!(f, "[{name}]")?,
+        }
+        if self.multiple {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
     #[inline(always)]
     pub const fn empty() -> Self {
         Self {
             short: None,
             long: None,
             help: None,
+            allow_hyphen_values: false,
+            negation: None,
+            arity: None,
+            delimiter: None,
+            positional: None,
+            required: false,
+            multiple: false,
+            flag: false,
+            global: false,
+            possible_values: None,
+            env: None,
+        }
+    }
+}
+
+/// How many values an option takes each time it's given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Arity {
+    /// One or more values, up to the next option.
+    pub const GREEDY: Self = Self {
+        min: 1,
+        max: usize::MAX,
+    };
+
+    #[inline(always)]
+    pub const fn exactly(count: usize) -> Self {
+        Self {
+            min: count,
+            max: count,
+        }
+    }
+    /// Panics if `range` is empty.
+    pub fn from_range(range: impl RangeBounds<usize>) -> Self {
+        let min = match range.start_bound() {
+            Bound::Included(&min) => min,
+            Bound::Excluded(&min) => min + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&max) => max,
+            Bound::Excluded(&max) => max.checked_sub(1).expect("Arity range is empty"),
+            Bound::Unbounded => usize::MAX,
+        };
+        assert!(min <= max, "Arity range is empty");
+        Self { min, max }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self { min, max } if min == max => write!(f, "{min}"),
+            Self {
+                min,
+                max: usize::MAX,
+            } => write!(f, "at least {min}"),
+            Self { min, max } => write!(f, "{min} to {max}"),
         }
     }
 }
@@ -0,0 +1,266 @@
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    ArgError, ArgumentList, ArgumentSink, Arguments, arg::ArgContext, parser::Globals,
+    source::ArgSource,
+};
+
+/// The subcommands [Arguments] dispatch to, see [Arguments::with_subcommands].
+///
+/// Sealed, only [NoCommands] and [Subcommands] implement it.
+pub trait CommandSet<'s>: sealed::Sealed {
+    /// Returns the index of the subcommand called `name`.
+    fn find(&self, name: &str) -> Option<usize>;
+    /// Parse every remaining argument with the subcommand at `index`, capturing unknown options
+    /// with the global options of `parent`.
+    fn parse(
+        &mut self,
+        index: usize,
+        source: &mut ArgSource<'_, 's>,
+        parent: &mut dyn Globals<'s>,
+    ) -> Result<(), ArgError<'s>>;
+    fn is_empty(&self) -> bool;
+    /// Show a global option of a parent command, and its default, in the help message of every
+    /// subcommand.
+    fn inherit(&mut self, ctx: &ArgContext, default: Option<&str>);
+    /// Write the name and help message of every subcommand.
+    fn write_help(&self, f: &mut Formatter<'_>) -> std::fmt::Result;
+    /// Returns the subcommand at `path`, such as `["remote", "add"]` for `tool remote add`.
+    fn find_help(&self, path: &[&str]) -> Option<&dyn CommandHelp>;
+}
+
+/// The help message of a subcommand, which may have subcommands of its own.
+pub trait CommandHelp {
+    fn write_help(&self, f: &mut Formatter<'_>) -> std::fmt::Result;
+    /// Returns the subcommand at `path`, or this one if `path` is empty.
+    fn find_help(&self, path: &[&str]) -> Option<&dyn CommandHelp>;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::NoCommands {}
+    impl<E> Sealed for super::Subcommands<'_, E> {}
+}
+
+/// No subcommands, the default for [Arguments].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoCommands;
+
+impl<'s> CommandSet<'s> for NoCommands {
+    #[inline(always)]
+    fn find(&self, _name: &str) -> Option<usize> {
+        None
+    }
+    #[inline(always)]
+    fn parse(
+        &mut self,
+        _index: usize,
+        _source: &mut ArgSource<'_, 's>,
+        _parent: &mut dyn Globals<'s>,
+    ) -> Result<(), ArgError<'s>> {
+        unreachable!()
+    }
+    #[inline(always)]
+    fn is_empty(&self) -> bool {
+        true
+    }
+    #[inline(always)]
+    fn inherit(&mut self, _ctx: &ArgContext, _default: Option<&str>) {}
+    #[inline(always)]
+    fn write_help(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
+        Ok(())
+    }
+    #[inline(always)]
+    fn find_help(&self, _path: &[&str]) -> Option<&dyn CommandHelp> {
+        None
+    }
+}
+
+/// Subcommands that turn into an `E` once chosen, such as a user-defined enum.
+pub struct Subcommands<'s, E> {
+    commands: Vec<Subcommand<'s, E>>,
+    chosen: Option<usize>,
+}
+
+impl<'s, E> Subcommands<'s, E> {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            chosen: None,
+        }
+    }
+    #[inline]
+    pub fn add(mut self, command: Subcommand<'s, E>) -> Self {
+        self.commands.push(command);
+        self
+    }
+    /// Returns the help message of the subcommand at `path`, such as `["remote", "add"]` for
+    /// `tool remote add`.
+    pub fn help_msg(&self, path: &[&str]) -> Option<impl Display + '_> {
+        Some(HelpMsg(self.find_help(path)?))
+    }
+    /// Returns the name of the subcommand that was chosen.
+    #[inline]
+    pub fn chosen(&self) -> Option<&'static str> {
+        Some(self.commands[self.chosen?].name)
+    }
+    /// Turn the chosen subcommand into an `E`.
+    #[inline]
+    pub fn into_chosen(mut self) -> Option<E> {
+        let command = self.commands.swap_remove(self.chosen?);
+        Some(command.command.finish())
+    }
+}
+
+impl<E> Default for Subcommands<'_, E> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'s, E> CommandSet<'s> for Subcommands<'s, E> {
+    #[inline]
+    fn find(&self, name: &str) -> Option<usize> {
+        self.commands
+            .iter()
+            .position(|command| command.name == name)
+    }
+    #[inline]
+    fn parse(
+        &mut self,
+        index: usize,
+        source: &mut ArgSource<'_, 's>,
+        parent: &mut dyn Globals<'s>,
+    ) -> Result<(), ArgError<'s>> {
+        self.chosen = Some(index);
+        let command = &mut self.commands[index];
+        command.command.parse(command.name, source, parent)
+    }
+    #[inline(always)]
+    fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    fn inherit(&mut self, ctx: &ArgContext, default: Option<&str>) {
+        for command in &mut self.commands {
+            command.command.inherit(ctx, default);
+        }
+    }
+    fn write_help(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Commands:")?;
+        for command in &self.commands {
+            let help = command.help.unwrap_or("[No help message]");
+            writeln!(f, "{} {help}", command.name)?;
+        }
+        Ok(())
+    }
+    fn find_help(&self, path: &[&str]) -> Option<&dyn CommandHelp> {
+        let (name, rest) = path.split_first()?;
+        let command = self.commands.iter().find(|command| command.name == *name)?;
+        command.command.find_help(rest)
+    }
+}
+
+/// A subcommand with its own [Arguments], turned into an `E` by `finish` once parsed.
+pub struct Subcommand<'s, E> {
+    name: &'static str,
+    help: Option<&'static str>,
+    command: Box<dyn Command<'s, E> + 's>,
+}
+
+impl<'s, E> Subcommand<'s, E> {
+    /// The program name of `arguments` defaults to `name`.
+    #[inline]
+    pub fn new<A, S, C, F>(name: &'static str, mut arguments: Arguments<A, S, C>, finish: F) -> Self
+    where
+        A: ArgumentList<'s> + 's,
+        S: ArgumentSink<'s> + 's,
+        C: CommandSet<'s> + 's,
+        F: FnOnce(Arguments<A, S, C>) -> E + 's,
+    {
+        arguments.program_name.get_or_insert(Cow::Borrowed(name));
+        Self {
+            name,
+            help: None,
+            command: Box::new(Nested { arguments, finish }),
+        }
+    }
+    #[inline(always)]
+    pub fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
+    }
+}
+
+trait Command<'s, E>: CommandHelp {
+    fn parse(
+        &mut self,
+        name: &'static str,
+        source: &mut ArgSource<'_, 's>,
+        parent: &mut dyn Globals<'s>,
+    ) -> Result<(), ArgError<'s>>;
+    fn inherit(&mut self, ctx: &ArgContext, default: Option<&str>);
+    fn finish(self: Box<Self>) -> E;
+}
+
+struct Nested<A, S, C, F> {
+    arguments: Arguments<A, S, C>,
+    finish: F,
+}
+
+impl<'s, A, S, C, F, E> Command<'s, E> for Nested<A, S, C, F>
+where
+    A: ArgumentList<'s>,
+    S: ArgumentSink<'s>,
+    C: CommandSet<'s>,
+    F: FnOnce(Arguments<A, S, C>) -> E,
+{
+    fn parse(
+        &mut self,
+        name: &'static str,
+        source: &mut ArgSource<'_, 's>,
+        parent: &mut dyn Globals<'s>,
+    ) -> Result<(), ArgError<'s>> {
+        let (mut parser, syntax) = self.arguments.parser(Some(parent));
+        source.nested(name, parser.scope.table, syntax, |source| {
+            parser.run(source)
+        })
+    }
+    fn inherit(&mut self, ctx: &ArgContext, default: Option<&str>) {
+        self.arguments.table.inherit(ctx, default);
+        self.arguments.commands.inherit(ctx, default);
+    }
+    fn finish(self: Box<Self>) -> E {
+        (self.finish)(self.arguments)
+    }
+}
+
+impl<'s, A, S, C, F> CommandHelp for Nested<A, S, C, F>
+where
+    A: ArgumentList<'s>,
+    S: ArgumentSink<'s>,
+    C: CommandSet<'s>,
+{
+    fn write_help(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.arguments.help_msg())
+    }
+    fn find_help(&self, path: &[&str]) -> Option<&dyn CommandHelp> {
+        match path {
+            [] => Some(self),
+            path => self.arguments.commands.find_help(path),
+        }
+    }
+}
+
+struct HelpMsg<'a>(&'a dyn CommandHelp);
+
+impl Display for HelpMsg<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.write_help(f)
+    }
+}
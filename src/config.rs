@@ -0,0 +1,92 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Option values read from a simple INI file, overridden by the command line.
+///
+/// Every line is either `key = value`, with `key` the long name of an option, or a `[name]`
+/// section header. Keys under a section go to the subcommand called `name`, keys before the first
+/// one to the top-level options. Blank lines and lines starting with `#` or `;` are ignored.
+///
+/// Values are read as if attached to the option, flags accept `true`, `false`, `1` or `0`.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: PathBuf,
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    /// None for the keys before the first section header.
+    name: Option<String>,
+    entries: Vec<ConfigEntry>,
+}
+
+/// A line of a [ConfigFile], numbered from 1.
+#[derive(Debug, Clone)]
+pub(crate) enum ConfigEntry {
+    Pair(usize, String, OsString),
+    /// Neither a pair nor a section header, reported once its section is read.
+    Malformed(usize, String),
+}
+
+impl ConfigFile {
+    /// Read and split the file at `path`.
+    pub fn read(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        let text = std::fs::read_to_string(&path)?;
+        Ok(Self::parse(path, &text))
+    }
+    /// Split `text`, reporting errors as coming from `path`.
+    pub fn parse(path: impl Into<PathBuf>, text: &str) -> Self {
+        let mut sections = vec![Section {
+            name: None,
+            entries: Vec::new(),
+        }];
+        for (number, line) in (1..).zip(text.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                sections.push(Section {
+                    name: Some(name.trim().to_owned()),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+            let entry = match line.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    let value = value.trim();
+                    let value = value
+                        .strip_prefix('"')
+                        .and_then(|value| value.strip_suffix('"'))
+                        .unwrap_or(value);
+                    ConfigEntry::Pair(number, key.trim().to_owned(), value.into())
+                }
+                _ => ConfigEntry::Malformed(number, line.to_owned()),
+            };
+            // The first section always exists
+            sections.last_mut().unwrap().entries.push(entry);
+        }
+        Self {
+            path: path.into(),
+            sections,
+        }
+    }
+    #[inline(always)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    /// Returns the entries for the subcommand called `name`, or the top-level ones if None.
+    pub(crate) fn entries(&self, name: Option<&str>) -> impl Iterator<Item = &ConfigEntry> {
+        self.sections
+            .iter()
+            .filter(move |section| section.name.as_deref() == name)
+            .flat_map(|section| &section.entries)
+    }
+}
@@ -0,0 +1,19 @@
+use std::{collections::HashMap, ffi::OsString, sync::OnceLock};
+
+/// The arguments of the current process, including `argv[0]`, read once and kept for the rest of
+/// the program.
+pub(crate) fn env_args() -> &'static [OsString] {
+    static ARGS: OnceLock<Box<[OsString]>> = OnceLock::new();
+    ARGS.get_or_init(|| std::env::args_os().collect())
+}
+
+/// The environment variables of the current process, read once on first use.
+pub(crate) fn env_vars() -> &'static HashMap<OsString, OsString> {
+    static VARS: OnceLock<HashMap<OsString, OsString>> = OnceLock::new();
+    VARS.get_or_init(|| std::env::vars_os().collect())
+}
+
+/// Returns true if the `POSIXLY_CORRECT` environment variable is set.
+pub(crate) fn posixly_correct() -> bool {
+    std::env::var_os("POSIXLY_CORRECT").is_some()
+}
@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+/// How many members of an [ArgGroup] may be given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupRule {
+    ExactlyOne,
+    AtMostOne,
+    AtLeastOne,
+}
+
+impl GroupRule {
+    #[inline(always)]
+    pub(crate) fn allows(self, given: usize) -> bool {
+        match self {
+            GroupRule::ExactlyOne => given == 1,
+            GroupRule::AtMostOne => given <= 1,
+            GroupRule::AtLeastOne => given >= 1,
+        }
+    }
+}
+
+impl Display for GroupRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupRule::ExactlyOne => f.write_str("exactly one"),
+            GroupRule::AtMostOne => f.write_str("at most one"),
+            GroupRule::AtLeastOne => f.write_str("at least one"),
+        }
+    }
+}
+
+/// Options named by their long name, of which only some may be given, such as `--file`, `--url`
+/// and `--stdin` selecting an input. See [crate::Arguments::with_group].
+#[derive(Debug, Clone, Copy)]
+pub struct ArgGroup {
+    /// Heading of the group in the help message.
+    pub name: &'static str,
+    pub rule: GroupRule,
+    pub members: &'static [&'static str],
+}
+
+impl ArgGroup {
+    #[inline(always)]
+    pub const fn new(
+        name: &'static str,
+        rule: GroupRule,
+        members: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            rule,
+            members,
+        }
+    }
+    #[inline(always)]
+    pub const fn exactly_one(name: &'static str, members: &'static [&'static str]) -> Self {
+        Self::new(name, GroupRule::ExactlyOne, members)
+    }
+    #[inline(always)]
+    pub const fn at_most_one(name: &'static str, members: &'static [&'static str]) -> Self {
+        Self::new(name, GroupRule::AtMostOne, members)
+    }
+    #[inline(always)]
+    pub const fn at_least_one(name: &'static str, members: &'static [&'static str]) -> Self {
+        Self::new(name, GroupRule::AtLeastOne, members)
+    }
+    /// Returns whether the option with the long name `long` is a member.
+    #[inline]
+    pub(crate) fn contains(&self, long: Option<&str>) -> bool {
+        long.is_some_and(|long| self.members.contains(&long))
+    }
+}
@@ -1,15 +1,195 @@
 use std::fmt::Display;
 
-use crate::{ArgumentList, Arguments};
+use crate::{
+    ArgumentList, Arguments, Syntax,
+    arg::ArgContext,
+    command::CommandSet,
+    group::{ArgGroup, GroupRule},
+};
 
-pub struct HelpMessage<'a, A, S>(pub(crate) &'a Arguments<A, S>);
+pub struct HelpMessage<'a, A, S, C>(pub(crate) &'a Arguments<A, S, C>);
 
-impl<'s, 'a, A: ArgumentList<'s>, S> Display for HelpMessage<'a, A, S> {
+impl<'s, 'a, A: ArgumentList<'s>, S, C: CommandSet<'s>> Display for HelpMessage<'a, A, S, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let args = self.0;
-        if let Some(name) = args.program_name {
-            writeln!(f, "{name}")?;
+        let syntax = args.syntax.as_ref().unwrap_or(&Syntax::GNU);
+        let is_option = |ctx: &ArgContext| ctx.short.is_some() || ctx.long.is_some();
+        let has_positionals = args.table.has_positionals();
+        let has_commands = !args.commands.is_empty();
+        let groups = &args.table.groups;
+        let is_grouped = |ctx: &ArgContext| args.table.is_grouped(ctx.long);
+        let mut has_required = false;
+        let mut has_options = !args.table.inherited.is_empty();
+        let mut grouped = Vec::new();
+        args.args.visit_ctxs(&mut |ctx| {
+            has_required |= ctx.required && is_option(ctx);
+            match is_grouped(ctx) {
+                true => grouped.push(*ctx),
+                false => has_options |= is_option(ctx),
+            }
+            Ok(())
+        })?;
+        if args.program_name.is_some()
+            || has_positionals
+            || has_commands
+            || has_required
+            || !groups.is_empty()
+        {
+            f.write_str("Usage:")?;
+            if let Some(name) = &args.program_name {
+                write!(f, " {name}")?;
+            }
+            if has_options {
+                f.write_str(" [OPTIONS]")?;
+            }
+            args.args
+                .visit_ctxs(&mut |ctx| match (ctx.short, ctx.long) {
+                    // Grouped options are shown with their group
+                    _ if !ctx.required || ctx.positional.is_some() || is_grouped(ctx) => Ok(()),
+                    (_, Some(long)) => {
+                        f.write_str(" ")?;
+                        syntax.write_long(long, f)
+                    }
+                    (Some(short), None) => match syntax.short_option_prefix() {
+                        Some(prefix) => write!(f, " {prefix}{short}"),
+                        None => Ok(()),
+                    },
+                    (None, None) => Ok(()),
+                })?;
+            for group in groups {
+                f.write_str(" ")?;
+                write_group_usage(group, &grouped, syntax, f)?;
+            }
+            args.args.visit_ctxs(&mut |ctx| match ctx.positional {
+                Some(_) => {
+                    f.write_str(" ")?;
+                    ctx.write_positional(f)
+                }
+                None => Ok(()),
+            })?;
+            if has_commands {
+                f.write_str(" [COMMAND]")?;
+            }
+            writeln!(f)?;
+        }
+        if !has_positionals && !has_commands && groups.is_empty() {
+            args.args
+                .visit_ctxs(&mut |ctx| write_option(args, ctx, f))?;
+            return write_inherited(args, f);
+        }
+        if has_positionals {
+            writeln!(f, "Arguments:")?;
+            args.args.visit_ctxs(&mut |ctx| match ctx.positional {
+                Some(_) => writeln!(f, "{ctx}"),
+                None => Ok(()),
+            })?;
+        }
+        if has_options {
+            writeln!(f, "Options:")?;
+            args.args.visit_ctxs(&mut |ctx| match ctx.positional {
+                None if !is_grouped(ctx) => write_option(args, ctx, f),
+                _ => Ok(()),
+            })?;
+            write_inherited(args, f)?;
+        }
+        for group in groups {
+            writeln!(f, "{}:", group.name)?;
+            args.args
+                .visit_ctxs(&mut |ctx| match group.contains(ctx.long) {
+                    true => write_option(args, ctx, f),
+                    false => Ok(()),
+                })?;
+        }
+        args.commands.write_help(f)
+    }
+}
+
+/// Write the global options of the commands `args` is nested in.
+fn write_inherited<A, S, C>(
+    args: &Arguments<A, S, C>,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    for ctx in &args.table.inherited {
+        write_option(args, ctx, f)?;
+    }
+    Ok(())
+}
+
+/// Write the members of `group` as shown in the usage line, such as
+/// `(--file <FILE> | --url <URL> | --stdin)`, looking up whether they take a value in `grouped`.
+fn write_group_usage(
+    group: &ArgGroup,
+    grouped: &[ArgContext],
+    syntax: &Syntax,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    let (open, close) = match group.rule {
+        GroupRule::AtMostOne => ('[', ']'),
+        GroupRule::ExactlyOne | GroupRule::AtLeastOne => ('(', ')'),
+    };
+    write!(f, "{open}")?;
+    for (i, member) in group.members.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" | ")?;
+        }
+        syntax.write_long(member, f)?;
+        let ctx = grouped.iter().find(|ctx| ctx.long == Some(member));
+        if ctx.is_some_and(|ctx| !ctx.flag) {
+            f.write_str(" ")?;
+            write_value_name(member, f)?;
+        }
+    }
+    write!(f, "{close}")
+}
+
+/// Write the placeholder for the value of the option `--{long}`, such as `<OUT_FILE>` for
+/// `--out-file`.
+fn write_value_name(long: &str, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("<")?;
+    for c in long.chars() {
+        match c {
+            '-' => f.write_str("_")?,
+            c => write!(f, "{}", c.to_uppercase())?,
+        }
+    }
+    f.write_str(">")
+}
+
+fn write_option<A, S, C>(
+    args: &Arguments<A, S, C>,
+    ctx: &ArgContext,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    let syntax = args.syntax.as_ref().unwrap_or(&Syntax::GNU);
+    ctx.write_with(syntax, f)?;
+    if ctx.required && ctx.positional.is_none() {
+        f.write_str(" [required]")?;
+    }
+    let values = ctx.possible_values.unwrap_or_default();
+    // Values with a help message are listed one per line below the option instead
+    let described = values.iter().any(|value| value.help.is_some());
+    if !values.is_empty() && !described {
+        f.write_str(" [possible values: ")?;
+        for (i, value) in values.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(f, "{separator}{}", value.name)?;
+        }
+        f.write_str("]")?;
+    }
+    if let Some(env) = ctx.env {
+        write!(f, " [env: {env}]")?;
+    }
+    if let Some(default) = args.table.default(ctx) {
+        write!(f, " [default: {default}]")?;
+    }
+    writeln!(f)?;
+    if described {
+        for value in values {
+            match value.help {
+                Some(help) => writeln!(f, "    {}: {help}", value.name)?,
+                None => writeln!(f, "    {}", value.name)?,
+            }
         }
-        args.args.visit_ctxs(&mut |ctx| writeln!(f, "{ctx}"))
     }
+    Ok(())
 }
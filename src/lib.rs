@@ -1,23 +1,54 @@
 #![expect(clippy::should_implement_trait)]
-use std::marker::PhantomData;
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    ffi::{OsStr, OsString},
+    marker::PhantomData,
+    ops::RangeBounds,
+    path::Path,
+    sync::Arc,
+};
 
-use rustc_hash::FxHashMap;
 use thiserror::Error;
 
 mod arg;
-use crate::arg::{Arg, ArgContext};
+mod command;
+mod config;
+mod env;
+mod group;
+use crate::arg::Relation;
+pub use crate::arg::{Arg, ArgContext, Arity, Negation};
+pub use group::{ArgGroup, GroupRule};
 
 mod help;
+mod parser;
+mod response;
 mod source;
+mod storage;
+mod syntax;
+mod table;
+mod value_enum;
 mod values;
+pub use command::{CommandSet, NoCommands, Subcommand, Subcommands};
+pub use config::ConfigFile;
 pub use help::HelpMessage;
-use source::*;
-pub use values::{OptFromStrWrapper, SetViaRef};
+use parser::{Globals, Parser, Scope};
+pub use source::{ArgSegment, ArgSource};
+pub use storage::ArgStorage;
+pub use syntax::{ShortStyle, Syntax};
+use table::OptionTable;
+pub use value_enum::{PossibleValue, ValueEnum};
+pub use values::{
+    Defaulted, FromStrValue, MapValue, OptFromStrWrapper, OptionalValue, Required, SetViaRef,
+    TryMap, Validated,
+};
 
 pub mod prelude {
     use std::str::FromStr;
 
-    pub use crate::{ArgError, Arguments, OptFromStrWrapper, arg::Arg};
+    pub use crate::{
+        ArgError, Arguments, FromStrValue, OptFromStrWrapper, OptionalValue, Required, arg::Arg,
+    };
     use crate::{ArgumentValue, values::SetViaRef};
 
     #[inline(always)]
@@ -34,6 +65,38 @@ pub mod prelude {
     {
         Arg::new(None)
     }
+    /// A positional argument that must be given.
+    #[inline(always)]
+    pub fn pos_required<'s, T>(name: &'static str) -> Arg<'s, Required<T>>
+    where
+        Required<T>: ArgumentValue<'s>,
+    {
+        Arg::new(Required(None)).positional(name)
+    }
+    /// A positional argument that can be left out.
+    #[inline(always)]
+    pub fn pos_optional<'s, T>(name: &'static str) -> Arg<'s, Option<T>>
+    where
+        Option<T>: ArgumentValue<'s>,
+    {
+        Arg::new(None).positional(name)
+    }
+    /// A positional argument taking every remaining positional value.
+    #[inline(always)]
+    pub fn pos_many<'s, T>(name: &'static str) -> Arg<'s, Vec<T>>
+    where
+        Vec<T>: ArgumentValue<'s>,
+    {
+        Arg::new(Vec::new()).positional(name)
+    }
+    /// An option taking an optional attached value, using `default` when none is given.
+    #[inline(always)]
+    pub fn opt_optional_value<'s, T>(default: T) -> Arg<'s, OptionalValue<T>>
+    where
+        OptionalValue<T>: ArgumentValue<'s>,
+    {
+        Arg::new(OptionalValue::new(default))
+    }
     #[inline(always)]
     pub fn opt_by_ref<'m, 's, T: ArgumentValue<'s>>(v: &'m mut T) -> Arg<'s, SetViaRef<'m, T>>
     where
@@ -45,22 +108,133 @@ pub mod prelude {
 
 #[derive(Debug, Clone, Error)]
 pub enum ArgError<'s> {
-    #[error("Invalid value({1}) for parameter {0}")]
-    InvalidValueForOpt(ArgContext, &'s str),
+    /// Carries the error the value was rejected with, if any, see [ArgError::invalid_value].
+    #[error("Invalid value({}) for parameter {}{}", .1.display(), .0, .2.as_ref().map(|err| format!(": {err}")).unwrap_or_default())]
+    InvalidValueForOpt(
+        Box<ArgContext>,
+        &'s OsStr,
+        #[source] Option<Arc<dyn std::error::Error + Send + Sync>>,
+    ),
+    #[error("Invalid value({}) for parameter {}, expected one of: {}", .1.display(), .0, .2.iter().map(|value| value.name).collect::<Vec<_>>().join(", "))]
+    InvalidChoice(Box<ArgContext>, &'s OsStr, &'static [PossibleValue]),
+    #[error("Invalid value({}) for parameter {}: {}", .1.display(), .0, .2)]
+    ValidationFailed(Box<ArgContext>, &'s OsStr, String),
     #[error("Missing value for parameter {0}")]
-    MissingValueForOpt(ArgContext),
+    MissingValueForOpt(Box<ArgContext>),
+    #[error("Expected key=value for parameter {}, found {}", .0, .1.display())]
+    MalformedPair(Box<ArgContext>, &'s OsStr),
+    #[error("Expected {} values for parameter {}, found {}", .1, .0, .2)]
+    WrongNumberOfValues(Box<ArgContext>, Arity, usize),
     #[error("Unkown short option: {0}")]
     UnknownShortOption(char),
-    #[error("Unkown long option: {0}")]
-    UnknownLongOption(&'s str),
+    #[error("Unkown long option: {}", .0.display())]
+    UnknownLongOption(&'s OsStr),
+    #[error("Ambiguous long option: {}, could be --{}", .0.display(), .1.join(", --"))]
+    AmbiguousLongOption(&'s OsStr, Vec<&'static str>),
+    #[error("Failed to read response file {}: {}", .0.display(), .1)]
+    ResponseFile(&'s Path, #[source] Arc<std::io::Error>),
+    #[error("Response file {} includes itself", .0.display())]
+    ResponseFileCycle(&'s Path),
+    #[error("Invalid UTF-8 in argument: {}", .0.display())]
+    InvalidUtf8(&'s OsStr),
+    #[error("Missing argument {0}")]
+    MissingPositional(Box<ArgContext>),
+    #[error("Missing required options: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    MissingRequired(Vec<ArgContext>),
+    #[error("Unexpected argument: {}", .0.display())]
+    ExtraPositional(&'s OsStr),
+    #[error("Unknown subcommand: {}", .0.display())]
+    UnknownSubcommand(&'s OsStr),
+    #[error("Invalid value in environment variable {0}: {1}")]
+    InvalidEnvValue(&'static str, Box<ArgError<'s>>),
+    #[error("Parameter {0} can't be used together with {1}")]
+    Conflict(Box<ArgContext>, Box<ArgContext>),
+    #[error("Parameter {0} requires {1}")]
+    MissingRequirement(Box<ArgContext>, Box<ArgContext>),
+    #[error("Parameter {0} is required unless {1} is given")]
+    RequiredUnless(Box<ArgContext>, Box<ArgContext>),
+    #[error("Expected {} of the {} options, found {}: {}", .0.rule, .0.name, .1, .2.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    GroupViolation(ArgGroup, usize, Vec<ArgContext>),
+    #[error("Failed to read config file {}: {}", .0.display(), .1)]
+    ConfigFileRead(&'s Path, #[source] Arc<std::io::Error>),
+    #[error("{}:{}: {}", .0.display(), .1, .2)]
+    InConfigFile(&'s Path, usize, Box<ArgError<'s>>),
+    #[error("Expected key = value or [section], found {0}")]
+    MalformedConfigLine(&'s str),
+}
+
+impl<'s> ArgError<'s> {
+    /// [ArgError::InvalidValueForOpt] keeping `err` as its source, such as the error of
+    /// [FromStr::from_str](std::str::FromStr::from_str).
+    ///
+    /// Errors that are only a message, such as a `String` or `&str`, are kept as one.
+    #[cold]
+    pub fn invalid_value(
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        err: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        ArgError::InvalidValueForOpt(Box::new(*ctx), value, Some(Arc::from(err.into())))
+    }
 }
 
 pub trait ArgumentValue<'s> {
+    /// What [Arguments::into_values] returns for this argument.
+    type Output;
+    /// Set for arguments that can be given more than once, such as `Vec<T>`, which also take
+    /// every remaining positional value.
+    const MULTIPLE: bool = false;
+    /// Every accepted value, see [FromArgValue::POSSIBLE_VALUES].
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = None;
+    /// Set for arguments that must be given, see [ArgumentValue::is_missing].
+    const REQUIRED: bool = false;
+    /// Set for arguments that take no value, such as `bool` and counts.
+    const FLAG: bool = false;
+
     fn capture(
         &mut self,
         ctx: &ArgContext,
         source: &mut ArgSource<'_, 's>,
     ) -> Result<(), ArgError<'s>>;
+    fn into_output(self) -> Self::Output;
+    /// Returns true if this argument is required but wasn't given.
+    #[inline(always)]
+    fn is_missing(&self) -> bool {
+        false
+    }
+}
+
+/// Types parsed from a single value, such as the `T` in `Option<T>`.
+pub trait FromArgValue<'s>: Sized {
+    /// Every accepted value, for types implementing [ValueEnum].
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = None;
+
+    fn from_arg_value(
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>>;
+}
+
+/// Flags that can be turned off again, see [Arg::negatable].
+///
+/// [ArgSource::is_negated] tells whether the negated option was given.
+pub trait NegatableValue<'s>: ArgumentValue<'s> {}
+
+/// Arguments holding a single value, which can fall back to a default or be checked and
+/// transformed, see [Arg::with_default] and [Arg::try_map].
+pub trait SingleValue<'s>: ArgumentValue<'s> {
+    /// The value without the wrapper, such as `T` for `Option<T>`.
+    type Value;
+    /// Parse `value` the way [ArgumentValue::capture] does.
+    fn parse_value(
+        &self,
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<Self::Value, ArgError<'s>>;
+    /// Returns the captured value, if any.
+    fn into_value(self) -> Option<Self::Value>;
 }
 
 #[expect(clippy::len_without_is_empty)]
@@ -70,7 +244,7 @@ pub trait ArgumentList<'s> {
     fn capture_short_arg(
         &mut self,
         args: &mut ArgSource<'_, 's>,
-        name: u8,
+        name: char,
     ) -> Result<bool, ArgError<'s>>;
     fn capture_long_arg(
         &mut self,
@@ -80,6 +254,8 @@ pub trait ArgumentList<'s> {
     fn into_values(self) -> Self::Values;
     /// Visit all [ArgContext]'s in index order.
     fn visit_ctxs<E>(&self, cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E>;
+    /// Visit the [ArgContext]'s of required arguments that weren't given, in index order.
+    fn visit_missing<E>(&self, cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E>;
     // TODO: Replace dynamic dispatch with contunuation-passing/capture-by-index
     /// Find the Arg corresponding to a given index
     fn capture_by_index(
@@ -87,6 +263,9 @@ pub trait ArgumentList<'s> {
         args: &mut ArgSource<'_, 's>,
         index: u16,
     ) -> Result<(), ArgError<'s>>;
+    /// Returns whether the argument at `index` was given.
+    fn is_seen(&self, index: u16) -> bool;
+    fn ctx_by_index(&self, index: u16) -> &ArgContext;
     fn len(&self) -> usize;
 }
 
@@ -98,7 +277,7 @@ impl<'s> ArgumentList<'s> for Empty {
     fn capture_short_arg(
         &mut self,
         _args: &mut ArgSource<'_, 's>,
-        _name: u8,
+        _name: char,
     ) -> Result<bool, ArgError<'s>> {
         Ok(false)
     }
@@ -114,6 +293,13 @@ impl<'s> ArgumentList<'s> for Empty {
     fn visit_ctxs<E>(&self, _cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E> {
         Ok(())
     }
+    #[inline(always)]
+    fn visit_missing<E>(
+        &self,
+        _cb: &mut impl FnMut(&ArgContext) -> Result<(), E>,
+    ) -> Result<(), E> {
+        Ok(())
+    }
 
     #[inline(always)]
     fn into_values(self) -> Self::Values {}
@@ -126,6 +312,14 @@ impl<'s> ArgumentList<'s> for Empty {
         unreachable!()
     }
     #[inline(always)]
+    fn is_seen(&self, _index: u16) -> bool {
+        unreachable!()
+    }
+    #[inline(always)]
+    fn ctx_by_index(&self, _index: u16) -> &ArgContext {
+        unreachable!()
+    }
+    #[inline(always)]
     fn len(&self) -> usize {
         0
     }
@@ -148,25 +342,41 @@ impl Arg<'_, u32> {
 impl<'s, T: ArgumentValue<'s> + Default> Arg<'s, T> {
     #[inline(always)]
     pub fn empty() -> Self {
-        Self {
-            ctx: ArgContext::empty(),
-            out: Default::default(),
-            _phantom: PhantomData,
-        }
+        Self::new(Default::default())
     }
 }
 impl<'s, T: ArgumentValue<'s>> Arg<'s, T> {
     #[inline(always)]
     pub fn new(val: T) -> Self {
         Self {
-            ctx: ArgContext::empty(),
+            ctx: ArgContext {
+                required: T::REQUIRED,
+                multiple: T::MULTIPLE,
+                flag: T::FLAG,
+                possible_values: T::POSSIBLE_VALUES,
+                ..ArgContext::empty()
+            },
             out: val,
+            relations: Vec::new(),
+            default: None,
+            seen: false,
             _phantom: PhantomData,
         }
     }
+    /// Also capture positional values, shown as `name` in the help message.
+    ///
+    /// Positional values go to positional arguments in the order they were added, with
+    /// arguments taking several values such as `Vec<T>` taking every remaining one. Values
+    /// left over go to the [ArgumentSink].
     #[inline(always)]
-    pub fn with_short(mut self, short: u8) -> Self {
-        self.ctx.short = short.try_into().ok();
+    pub fn positional(mut self, name: &'static str) -> Self {
+        self.ctx.positional = Some(name);
+        self
+    }
+    /// Set the short name of this argument, `'\0'` clears it.
+    #[inline(always)]
+    pub fn with_short(mut self, short: impl Into<char>) -> Self {
+        self.ctx.short = Some(short.into()).filter(|&short| short != '\0');
         self
     }
     #[inline(always)]
@@ -174,29 +384,223 @@ impl<'s, T: ArgumentValue<'s>> Arg<'s, T> {
         self.ctx.long = Some(long);
         self
     }
+    /// Accept values starting with `-` for this argument, such as `--offset -5`.
+    ///
+    /// Without this, only values that look like negative numbers are accepted, and only when they
+    /// don't name a registered short option.
+    #[inline(always)]
+    pub fn allow_hyphen_values(mut self) -> Self {
+        self.ctx.allow_hyphen_values = true;
+        self
+    }
+    /// Take between `range.start` and `range.end` values each time this option is given, such as
+    /// `--files a b c`.
+    ///
+    /// Only used by arguments taking several values. `Option<Vec<T>>` takes [Arity::GREEDY] by
+    /// default, and collections such as `Vec<T>` take a single value. Panics if `range` is empty.
+    #[inline]
+    pub fn with_arity(mut self, range: impl RangeBounds<usize>) -> Self {
+        self.ctx.arity = Some(Arity::from_range(range));
+        self
+    }
+    /// Split every value on `delimiter`, such as `--tags=a,b,c`.
+    ///
+    /// Only used by arguments taking several values, such as `Vec<T>`. Panics if `delimiter`
+    /// isn't ASCII.
+    #[inline]
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        assert!(delimiter.is_ascii(), "Delimiters must be ASCII");
+        self.ctx.delimiter = Some(delimiter);
+        self
+    }
     #[inline(always)]
     pub fn with_help(mut self, help: &'static str) -> Self {
         self.ctx.help = Some(help);
         self
     }
+    /// Fail parsing with [ArgError::MissingRequired] unless this option is given.
+    #[inline(always)]
+    pub fn required(mut self) -> Self {
+        self.ctx.required = true;
+        self
+    }
+    /// Fail parsing with [ArgError::Conflict] if this argument and the option `--{long}` are
+    /// both given.
+    ///
+    /// Like every relation, adding this argument panics unless `--{long}` was added before it.
+    #[inline]
+    pub fn conflicts_with(mut self, long: &'static str) -> Self {
+        self.relations.push(Relation::ConflictsWith(long));
+        self
+    }
+    /// Fail parsing with [ArgError::MissingRequirement] if this argument is given without the
+    /// option `--{long}`.
+    #[inline]
+    pub fn requires(mut self, long: &'static str) -> Self {
+        self.relations.push(Relation::Requires(long));
+        self
+    }
+    /// Fail parsing with [ArgError::RequiredUnless] if neither this argument nor the option
+    /// `--{long}` is given.
+    #[inline]
+    pub fn required_unless(mut self, long: &'static str) -> Self {
+        self.relations.push(Relation::RequiredUnless(long));
+        self
+    }
+    /// Also set the flag `--{long}` when this argument is given, as if it was given too.
+    ///
+    /// Adding this argument panics if `--{long}` takes a value.
+    #[inline]
+    pub fn implies(mut self, long: &'static str) -> Self {
+        self.relations.push(Relation::Implies(long));
+        self
+    }
+    /// Read the value from the environment variable `var` when this argument isn't given.
+    ///
+    /// The variable takes precedence over [Arg::with_default]. Flags accept `true`, `false`, `1`
+    /// or `0`, counts a number.
+    #[inline(always)]
+    pub fn with_env(mut self, var: &'static str) -> Self {
+        self.ctx.env = Some(var);
+        self
+    }
+    /// Recognise this option in every subcommand too, writing to the same value.
+    ///
+    /// Global options are listed in the help message of every subcommand.
+    #[inline(always)]
+    pub fn global(mut self) -> Self {
+        self.ctx.global = true;
+        self
+    }
+}
+
+impl<'s, T: SingleValue<'s>> Arg<'s, T> {
+    /// Use `default` when this option isn't given, so the value is a plain `T::Value`.
+    ///
+    /// The default is shown in the help message, formatted with [Display](std::fmt::Display).
+    #[inline]
+    pub fn with_default(self, default: T::Value) -> Arg<'s, Defaulted<'s, T>>
+    where
+        T::Value: std::fmt::Display,
+    {
+        let shown = default.to_string();
+        self.defaulted(default, Cow::Owned(shown))
+    }
+    /// Same as [Arg::with_default], parsing `default` like a value on the command line.
+    ///
+    /// # Panics
+    /// If `default` isn't a valid value for this option.
+    #[inline]
+    pub fn with_default_str(self, default: &'static str) -> Arg<'s, Defaulted<'s, T>> {
+        let value = self
+            .out
+            .parse_value(&self.ctx, OsStr::new(default), &ArgSource::new(&[]))
+            .unwrap_or_else(|err| panic!("Invalid default value: {err}"));
+        self.defaulted(value, Cow::Borrowed(default))
+    }
+    #[inline(always)]
+    fn defaulted(
+        mut self,
+        default: T::Value,
+        shown: Cow<'static, str>,
+    ) -> Arg<'s, Defaulted<'s, T>> {
+        self.default = Some(shown);
+        self.wrap(|value| Defaulted { value, default })
+    }
+    /// Transform every value with `map` as it's captured, failing with
+    /// [ArgError::ValidationFailed] and the message it returns on errors.
+    #[inline]
+    pub fn try_map<U, F: MapValue<T::Value, U>>(self, map: F) -> Arg<'s, TryMap<T, U, F>> {
+        self.wrap(|inner| TryMap {
+            inner,
+            value: None,
+            map,
+        })
+    }
+    /// Check every value with `check` as it's captured, failing with
+    /// [ArgError::ValidationFailed] and the message it returns on errors.
+    #[inline]
+    pub fn validate(
+        self,
+        check: impl Fn(&T::Value) -> Result<(), String>,
+    ) -> Arg<'s, Validated<'s, T, impl MapValue<T::Value, T::Value>>> {
+        self.try_map(move |value| check(&value).map(|()| value))
+    }
+    /// Only accept values within `range`, see [Arg::validate].
+    #[inline]
+    pub fn range(
+        self,
+        range: impl RangeBounds<T::Value>,
+    ) -> Arg<'s, Validated<'s, T, impl MapValue<T::Value, T::Value>>>
+    where
+        T::Value: PartialOrd + std::fmt::Display,
+    {
+        self.validate(move |value| match range.contains(value) {
+            true => Ok(()),
+            false => Err(format!("Expected {}", values::describe_range(&range))),
+        })
+    }
+    /// Reject empty values, see [Arg::validate].
+    #[inline]
+    pub fn non_empty(self) -> Arg<'s, Validated<'s, T, impl MapValue<T::Value, T::Value>>>
+    where
+        T::Value: AsRef<OsStr>,
+    {
+        self.validate(|value| match value.as_ref().is_empty() {
+            true => Err("Expected a non-empty value".to_owned()),
+            false => Ok(()),
+        })
+    }
+}
+
+impl<'s, T: ArgumentValue<'s>> Arg<'s, T> {
+    /// Wrap the value of this argument, keeping everything else.
+    #[inline(always)]
+    fn wrap<O: ArgumentValue<'s>>(self, wrap: impl FnOnce(T) -> O) -> Arg<'s, O> {
+        Arg {
+            ctx: self.ctx,
+            out: wrap(self.out),
+            relations: self.relations,
+            default: self.default,
+            seen: self.seen,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'s, T: NegatableValue<'s>> Arg<'s, T> {
+    /// Also accept `--no-{long}`, turning this flag off again.
+    ///
+    /// The last of the two on the command line wins.
+    #[inline(always)]
+    pub fn negatable(mut self) -> Self {
+        self.ctx.negation = Some(Negation::Prefixed);
+        self
+    }
+    /// Same as [Arg::negatable], using `--{negation}` instead of `--no-{long}`.
+    #[inline(always)]
+    pub fn with_negation(mut self, negation: &'static str) -> Self {
+        self.ctx.negation = Some(Negation::Named(negation));
+        self
+    }
 }
 
 impl<'s, T: ArgumentValue<'s>> ArgumentList<'s> for Arg<'s, T> {
-    type Values = T;
+    type Values = T::Output;
 
     #[inline(always)]
     fn capture_short_arg(
         &mut self,
         args: &mut ArgSource<'_, 's>,
-        name: u8,
+        name: char,
     ) -> Result<bool, ArgError<'s>> {
         let Some(short) = self.ctx.short else {
             return Ok(false);
         };
-        if short.get() != name {
+        if short != name {
             return Ok(false);
         }
-        self.out.capture(&self.ctx, args)?;
+        self.capture_by_index(args, 0)?;
         Ok(true)
     }
     #[inline(always)]
@@ -211,19 +615,26 @@ impl<'s, T: ArgumentValue<'s>> ArgumentList<'s> for Arg<'s, T> {
         if name != long {
             return Ok(false);
         }
-        self.out.capture(&self.ctx, args)?;
+        self.capture_by_index(args, 0)?;
         Ok(true)
     }
 
     #[inline(always)]
     fn into_values(self) -> Self::Values {
-        self.out
+        self.out.into_output()
     }
     #[inline(always)]
     fn visit_ctxs<E>(&self, cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E> {
         cb(&self.ctx)
     }
     #[inline(always)]
+    fn visit_missing<E>(&self, cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E> {
+        if self.out.is_missing() || (self.ctx.required && !self.seen) {
+            return cb(&self.ctx);
+        }
+        Ok(())
+    }
+    #[inline(always)]
     fn capture_by_index(
         &mut self,
         args: &mut ArgSource<'_, 's>,
@@ -232,9 +643,24 @@ impl<'s, T: ArgumentValue<'s>> ArgumentList<'s> for Arg<'s, T> {
         if index != 0 {
             unreachable!()
         }
+        self.seen = true;
         self.out.capture(&self.ctx, args)
     }
     #[inline(always)]
+    fn is_seen(&self, index: u16) -> bool {
+        if index != 0 {
+            unreachable!()
+        }
+        self.seen
+    }
+    #[inline(always)]
+    fn ctx_by_index(&self, index: u16) -> &ArgContext {
+        if index != 0 {
+            unreachable!()
+        }
+        &self.ctx
+    }
+    #[inline(always)]
     fn len(&self) -> usize {
         1
     }
@@ -245,13 +671,13 @@ pub struct More<'s, T: ArgumentValue<'s>, A: ArgumentList<'s>> {
     pub arg: Arg<'s, T>,
 }
 impl<'s, T: ArgumentValue<'s>, A: ArgumentList<'s>> ArgumentList<'s> for More<'s, T, A> {
-    type Values = (A::Values, T);
+    type Values = (A::Values, T::Output);
 
     #[inline]
     fn capture_short_arg(
         &mut self,
         args: &mut ArgSource<'_, 's>,
-        name: u8,
+        name: char,
     ) -> Result<bool, ArgError<'s>> {
         if self.arg.capture_short_arg(args, name)? {
             return Ok(true);
@@ -280,50 +706,51 @@ impl<'s, T: ArgumentValue<'s>, A: ArgumentList<'s>> ArgumentList<'s> for More<'s
         cb(&self.arg.ctx)
     }
     #[inline(always)]
+    fn visit_missing<E>(&self, cb: &mut impl FnMut(&ArgContext) -> Result<(), E>) -> Result<(), E> {
+        self.rest.visit_missing(cb)?;
+        self.arg.visit_missing(cb)
+    }
+    #[inline(always)]
     fn capture_by_index(
         &mut self,
         args: &mut ArgSource<'_, 's>,
         index: u16,
     ) -> Result<(), ArgError<'s>> {
         if index == 0 {
-            return self.arg.out.capture(&self.arg.ctx, args);
+            return self.arg.capture_by_index(args, 0);
         }
         self.rest.capture_by_index(args, index - 1)
     }
     #[inline(always)]
+    fn is_seen(&self, index: u16) -> bool {
+        if index == 0 {
+            return self.arg.seen;
+        }
+        self.rest.is_seen(index - 1)
+    }
+    #[inline(always)]
+    fn ctx_by_index(&self, index: u16) -> &ArgContext {
+        if index == 0 {
+            return &self.arg.ctx;
+        }
+        self.rest.ctx_by_index(index - 1)
+    }
+    #[inline(always)]
     fn len(&self) -> usize {
         1 + self.rest.len()
     }
 }
 
-pub struct Arguments<A, S> {
+pub struct Arguments<A, S, C = NoCommands> {
     pub args: A,
     sink: S,
-    program_name: Option<&'static str>,
-    // NOTE: Intentionally 1 less than [u8::MAX], as 0 isn't a valid shorthand
-    short_lut: [u16; 255],
-    long_map: FxHashMap<&'static str, u16>,
-}
-
-const fn empty_lut() -> [u16; 255] {
-    [u16::MAX; _]
-}
-
-fn add_to_lut(lut: &mut [u16; 255], ctx: &ArgContext) {
-    lut.iter_mut()
-        .filter(|&&mut idx| idx != u16::MAX)
-        .for_each(|idx| *idx += 1);
-    let Some(short) = ctx.short else {
-        return;
-    };
-    lut[short.get().wrapping_sub(1) as usize] = 0;
-}
-
-fn add_to_map(map: &mut FxHashMap<&'static str, u16>, ctx: &ArgContext) {
-    map.values_mut().for_each(|idx| *idx += 1);
-    if let Some(long) = ctx.long {
-        map.insert(long, 0);
-    }
+    commands: C,
+    program_name: Option<Cow<'static, str>>,
+    table: OptionTable,
+    /// Set when options aren't written with [Syntax::GNU].
+    syntax: Option<Syntax>,
+    /// Set when options end at the first positional value.
+    stop_at_positional: bool,
 }
 
 impl Arguments<Empty, ()> {
@@ -338,86 +765,131 @@ impl<S> Arguments<Empty, S> {
         Self {
             args: Empty,
             sink,
+            commands: NoCommands,
             program_name: None,
-            short_lut: empty_lut(),
-            long_map: FxHashMap::default(),
+            table: OptionTable::new(),
+            syntax: None,
+            stop_at_positional: false,
         }
     }
+}
+impl<S, C> Arguments<Empty, S, C> {
     #[inline]
-    pub fn add<'s, T: ArgumentValue<'s>>(self, argument: Arg<'s, T>) -> Arguments<Arg<'s, T>, S> {
+    pub fn add<'s, T: ArgumentValue<'s>>(
+        self,
+        mut argument: Arg<'s, T>,
+    ) -> Arguments<Arg<'s, T>, S, C>
+    where
+        C: CommandSet<'s>,
+    {
         let Self {
             args: _,
             sink,
+            mut commands,
             program_name,
-            mut short_lut,
-            mut long_map,
+            mut table,
+            syntax,
+            stop_at_positional,
         } = self;
-        add_to_lut(&mut short_lut, &argument.ctx);
-        add_to_map(&mut long_map, &argument.ctx);
+        let default = argument.default.take();
+        if argument.ctx.global {
+            commands.inherit(&argument.ctx, default.as_deref());
+        }
+        table.add(
+            &argument.ctx,
+            std::mem::take(&mut argument.relations),
+            default,
+        );
         Arguments {
             args: argument,
             sink,
+            commands,
             program_name,
-            short_lut,
-            long_map,
+            table,
+            syntax,
+            stop_at_positional,
         }
     }
 }
 
-impl<'s, T: ArgumentValue<'s>, S> Arguments<Arg<'s, T>, S> {
+impl<'s, T: ArgumentValue<'s>, S, C: CommandSet<'s>> Arguments<Arg<'s, T>, S, C> {
     #[inline]
     pub fn add<O: ArgumentValue<'s>>(
         self,
-        argument: Arg<'s, O>,
-    ) -> Arguments<More<'s, O, Arg<'s, T>>, S> {
+        mut argument: Arg<'s, O>,
+    ) -> Arguments<More<'s, O, Arg<'s, T>>, S, C> {
         let Self {
             args,
             sink,
+            mut commands,
             program_name,
-            mut short_lut,
-            mut long_map,
+            mut table,
+            syntax,
+            stop_at_positional,
         } = self;
-        add_to_lut(&mut short_lut, &argument.ctx);
-        add_to_map(&mut long_map, &argument.ctx);
+        let default = argument.default.take();
+        if argument.ctx.global {
+            commands.inherit(&argument.ctx, default.as_deref());
+        }
+        table.add(
+            &argument.ctx,
+            std::mem::take(&mut argument.relations),
+            default,
+        );
         Arguments {
             args: More {
                 rest: args,
                 arg: argument,
             },
             sink,
+            commands,
             program_name,
-            short_lut,
-            long_map,
+            table,
+            syntax,
+            stop_at_positional,
         }
     }
 }
 
-impl<'s, T: ArgumentValue<'s>, A: ArgumentList<'s>, S> Arguments<More<'s, T, A>, S> {
+impl<'s, T: ArgumentValue<'s>, A: ArgumentList<'s>, S, C: CommandSet<'s>>
+    Arguments<More<'s, T, A>, S, C>
+{
     #[inline]
     pub fn add<O: ArgumentValue<'s>>(
         self,
-        argument: Arg<'s, O>,
-    ) -> Arguments<More<'s, O, More<'s, T, A>>, S> {
+        mut argument: Arg<'s, O>,
+    ) -> Arguments<More<'s, O, More<'s, T, A>>, S, C> {
         let Self {
             args,
             sink,
+            mut commands,
             program_name,
-            mut short_lut,
-            mut long_map,
+            mut table,
+            syntax,
+            stop_at_positional,
         } = self;
         let len = args.len();
         assert!(len < u16::MAX as usize);
-        add_to_lut(&mut short_lut, &argument.ctx);
-        add_to_map(&mut long_map, &argument.ctx);
+        let default = argument.default.take();
+        if argument.ctx.global {
+            commands.inherit(&argument.ctx, default.as_deref());
+        }
+        table.add(
+            &argument.ctx,
+            std::mem::take(&mut argument.relations),
+            default,
+        );
         Arguments {
             args: More {
                 rest: args,
                 arg: argument,
             },
             sink,
+            commands,
             program_name,
-            short_lut,
-            long_map,
+            table,
+            syntax,
+            stop_at_positional,
         }
     }
 }
@@ -430,14 +902,41 @@ impl Default for Arguments<Empty, ()> {
 }
 
 pub trait ArgumentSink<'s> {
+    /// Set by sinks that drop every value, so that values left over after every positional
+    /// argument was given fail with [ArgError::ExtraPositional] instead.
+    const DISCARDS: bool = false;
+
     fn consume_value(&mut self, value: &'s str) -> Result<(), ArgError<'s>>;
+    /// Consume a value that may not be valid UTF-8.
+    ///
+    /// Forwards to [ArgumentSink::consume_value] unless overridden, rejecting values that aren't
+    /// valid UTF-8.
+    #[inline(always)]
+    fn consume_os_value(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        let value = value.to_str().ok_or(ArgError::InvalidUtf8(value))?;
+        self.consume_value(value)
+    }
+    /// Consume a value that followed the `--` terminator.
+    ///
+    /// Forwards to [ArgumentSink::consume_os_value] unless overridden, see [SplitSink] for
+    /// keeping trailing values apart.
+    #[inline(always)]
+    fn consume_trailing(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        self.consume_os_value(value)
+    }
 }
 
 impl<'s> ArgumentSink<'s> for () {
+    const DISCARDS: bool = true;
+
     #[inline(always)]
     fn consume_value(&mut self, _value: &'s str) -> Result<(), ArgError<'s>> {
         Ok(())
     }
+    #[inline(always)]
+    fn consume_os_value(&mut self, _value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        Ok(())
+    }
 }
 
 impl<'s> ArgumentSink<'s> for Vec<&'s str> {
@@ -448,6 +947,32 @@ impl<'s> ArgumentSink<'s> for Vec<&'s str> {
     }
 }
 
+impl<'s> ArgumentSink<'s> for Vec<&'s OsStr> {
+    #[inline(always)]
+    fn consume_value(&mut self, value: &'s str) -> Result<(), ArgError<'s>> {
+        self.push(OsStr::new(value));
+        Ok(())
+    }
+    #[inline(always)]
+    fn consume_os_value(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+impl<'s> ArgumentSink<'s> for Vec<&'s Path> {
+    #[inline(always)]
+    fn consume_value(&mut self, value: &'s str) -> Result<(), ArgError<'s>> {
+        self.push(Path::new(value));
+        Ok(())
+    }
+    #[inline(always)]
+    fn consume_os_value(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        self.push(Path::new(value));
+        Ok(())
+    }
+}
+
 impl<'s, C: FnMut(&'s str) -> Result<(), ArgError<'s>>> ArgumentSink<'s> for C {
     #[inline(always)]
     fn consume_value(&mut self, value: &'s str) -> Result<(), ArgError<'s>> {
@@ -455,68 +980,294 @@ impl<'s, C: FnMut(&'s str) -> Result<(), ArgError<'s>>> ArgumentSink<'s> for C {
     }
 }
 
-impl<'s, A: ArgumentList<'s>, S: ArgumentSink<'s>> Arguments<A, S> {
+/// A sink that sends values following the `--` terminator to a separate `trailing` sink.
+#[derive(Debug, Default, Clone)]
+pub struct SplitSink<S, T> {
+    pub values: S,
+    pub trailing: T,
+}
+
+impl<'s, S: ArgumentSink<'s>, T: ArgumentSink<'s>> ArgumentSink<'s> for SplitSink<S, T> {
     #[inline(always)]
-    pub fn with_sink<NS: ArgumentSink<'s>>(self, new_sink: NS) -> Arguments<A, NS> {
+    fn consume_value(&mut self, value: &'s str) -> Result<(), ArgError<'s>> {
+        self.values.consume_value(value)
+    }
+    #[inline(always)]
+    fn consume_os_value(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        self.values.consume_os_value(value)
+    }
+    #[inline(always)]
+    fn consume_trailing(&mut self, value: &'s OsStr) -> Result<(), ArgError<'s>> {
+        self.trailing.consume_os_value(value)
+    }
+}
+
+impl<'s, A: ArgumentList<'s>, S: ArgumentSink<'s>, C: CommandSet<'s>> Arguments<A, S, C> {
+    #[inline(always)]
+    pub fn with_sink<NS: ArgumentSink<'s>>(self, new_sink: NS) -> Arguments<A, NS, C> {
         let Self {
             args,
             sink: _sink,
+            commands,
             program_name,
-            short_lut,
-            long_map,
+            table,
+            syntax,
+            stop_at_positional,
         } = self;
         Arguments {
             args,
             sink: new_sink,
+            commands,
             program_name,
-            short_lut,
-            long_map,
+            table,
+            syntax,
+            stop_at_positional,
         }
     }
+    /// Send values following the `--` terminator to `trailing` instead of the current sink.
+    #[inline(always)]
+    pub fn with_trailing_sink<T: ArgumentSink<'s>>(
+        self,
+        trailing: T,
+    ) -> Arguments<A, SplitSink<S, T>, C> {
+        let Self {
+            args,
+            sink,
+            commands,
+            program_name,
+            table,
+            syntax,
+            stop_at_positional,
+        } = self;
+        Arguments {
+            args,
+            sink: SplitSink {
+                values: sink,
+                trailing,
+            },
+            commands,
+            program_name,
+            table,
+            syntax,
+            stop_at_positional,
+        }
+    }
+    /// Accept unambiguous prefixes of long options, such as `--verb` for `--verbose`.
+    ///
+    /// Exact matches always win over prefixes.
+    #[inline(always)]
+    pub fn allow_abbreviations(mut self) -> Self {
+        self.table.abbreviations = true;
+        self
+    }
+    /// Check how many members of `group` are given once parsing is done.
+    ///
+    /// The members are listed under their own heading in the help message. Panics unless every
+    /// member was added before.
+    #[inline]
+    pub fn with_group(mut self, group: ArgGroup) -> Self {
+        self.table.add_group(group);
+        self
+    }
+    /// Read options written with `syntax` instead of [Syntax::GNU].
+    #[inline(always)]
+    pub fn with_syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = (syntax != Syntax::GNU).then_some(syntax);
+        self
+    }
+    /// Stop reading options at the first positional value, like `env` and `sudo`.
+    ///
+    /// The value and every argument after it are passed on verbatim to
+    /// [ArgumentSink::consume_trailing], see [Arguments::with_trailing_sink] to keep them apart.
+    #[inline(always)]
+    pub fn stop_at_first_positional(mut self) -> Self {
+        self.stop_at_positional = true;
+        self
+    }
+    /// Same as [Arguments::stop_at_first_positional] if the `POSIXLY_CORRECT` environment
+    /// variable is set.
+    #[inline]
+    pub fn honor_posixly_correct(mut self) -> Self {
+        self.stop_at_positional |= env::posixly_correct();
+        self
+    }
     #[inline(always)]
-    pub fn with_program_name(mut self, name: &'static str) -> Self {
-        self.program_name = Some(name);
+    pub fn with_program_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.program_name = Some(name.into());
         self
     }
     #[inline(always)]
     pub fn parse(&mut self, args: &[&'s str]) -> Result<(), ArgError<'s>> {
-        let mut source = ArgSource::new(args);
-        while let Some(segment) = source.next() {
-            match segment {
-                ArgSegment::Short(0) => {
-                    return Err(ArgError::UnknownShortOption('\0'));
-                }
-                ArgSegment::Short(short) => {
-                    let idx = self.short_lut[(short - 1) as usize];
-                    if idx == u16::MAX {
-                        return Err(ArgError::UnknownShortOption(short as char));
-                    }
-                    self.args.capture_by_index(&mut source, idx)?;
-                }
-                ArgSegment::Long(long) => {
-                    let Some(&idx) = self.long_map.get(long) else {
-                        return Err(ArgError::UnknownLongOption(long));
-                    };
-                    self.args.capture_by_index(&mut source, idx)?;
-                }
-                ArgSegment::Value(val) => {
-                    self.sink.consume_value(val)?;
-                }
+        self.parse_source(ArgSource::new(args))
+    }
+    /// Parse arguments that may not be valid UTF-8.
+    ///
+    /// Option names still have to be valid UTF-8, values are passed on untouched.
+    #[inline(always)]
+    pub fn parse_os(&mut self, args: &[&'s OsStr]) -> Result<(), ArgError<'s>> {
+        self.parse_source(ArgSource::new_os(args))
+    }
+    /// Parse the arguments of the current process.
+    ///
+    /// `argv[0]` is used as the program name, unless one was set with
+    /// [Arguments::with_program_name].
+    #[inline]
+    pub fn parse_env(&mut self) -> Result<(), ArgError<'s>> {
+        if let Some(program) = env::env_args().first() {
+            self.default_program_name(program);
+        }
+        self.parse_source(ArgSource::from_env())
+    }
+    /// Parse `args`, with the first one being the program name like in [Arguments::parse_env].
+    ///
+    /// The arguments are moved into `storage`, so the values parsed from them live as long as it
+    /// does.
+    #[inline]
+    pub fn parse_from<I>(&mut self, args: I, storage: &'s ArgStorage) -> Result<(), ArgError<'s>>
+    where
+        I: IntoIterator<Item: Into<OsString>>,
+    {
+        let args = storage.alloc(args.into_iter().map(Into::into).collect());
+        self.parse_owned(args)
+    }
+    fn parse_owned(&mut self, mut args: &'s [OsString]) -> Result<(), ArgError<'s>> {
+        if let Some((program, rest)) = args.split_first() {
+            self.default_program_name(program);
+            args = rest;
+        }
+        self.parse_source(ArgSource::new_owned(args))
+    }
+    fn default_program_name(&mut self, program: &OsStr) {
+        self.program_name.get_or_insert_with(|| {
+            let name = Path::new(program).file_name().unwrap_or(program);
+            Cow::Owned(name.to_string_lossy().into_owned())
+        });
+    }
+    /// Parse `args`, replacing every `@file` argument with the arguments listed in `file`.
+    ///
+    /// See [ArgSource::with_response_files].
+    #[inline(always)]
+    pub fn parse_with_response_files(
+        &mut self,
+        args: &[&'s str],
+        storage: &'s ArgStorage,
+    ) -> Result<(), ArgError<'s>> {
+        self.parse_source(ArgSource::new(args).with_response_files(storage))
+    }
+    /// Same as [Arguments::parse], reading the options that weren't given from the config file
+    /// named by `--{long}`, see [ArgSource::with_config_option].
+    #[inline]
+    pub fn parse_with_config(
+        &mut self,
+        args: &[&'s str],
+        long: &'static str,
+        storage: &'s ArgStorage,
+    ) -> Result<(), ArgError<'s>> {
+        self.parse_source(ArgSource::new(args).with_config_option(long, storage)?)
+    }
+    #[inline(always)]
+    pub fn parse_source(&mut self, source: ArgSource<'_, 's>) -> Result<(), ArgError<'s>> {
+        let (mut parser, syntax) = self.parser(None);
+        let mut source = source.configure(parser.scope.table, syntax);
+        let result = parser.run(&mut source);
+        // A response file that couldn't be read takes precedence over the error it caused
+        match source.take_error() {
+            Some(err) => Err(err),
+            None => result,
+        }
+    }
+    #[inline(always)]
+    pub(crate) fn parser<'p>(
+        &'p mut self,
+        parent: Option<&'p mut dyn Globals<'s>>,
+    ) -> (Parser<'p, 's, A, S, C>, Option<&'p Syntax>) {
+        let parser = Parser {
+            scope: Scope {
+                args: &mut self.args,
+                table: &self.table,
+                parent,
+            },
+            sink: &mut self.sink,
+            commands: &mut self.commands,
+            stop_at_positional: self.stop_at_positional,
+        };
+        (parser, self.syntax.as_ref())
+    }
+    pub fn help_msg<'a>(&'a self) -> HelpMessage<'a, A, S, C> {
+        HelpMessage(self)
+    }
+}
+
+impl<'s, A: ArgumentList<'s>, S: ArgumentSink<'s>> Arguments<A, S> {
+    /// Dispatch to one of `commands` on the first positional value naming one of them.
+    ///
+    /// The subcommand parses every argument after its name, see [Arguments::into_values] for
+    /// getting the chosen one.
+    #[inline]
+    pub fn with_subcommands<E>(
+        self,
+        mut commands: Subcommands<'s, E>,
+    ) -> Arguments<A, S, Subcommands<'s, E>> {
+        for ctx in &self.table.inherited {
+            commands.inherit(ctx, self.table.default(ctx));
+        }
+        let _ = self.args.visit_ctxs(&mut |ctx| {
+            if ctx.global {
+                commands.inherit(ctx, self.table.default(ctx));
             }
+            Ok::<_, Infallible>(())
+        });
+        let Self {
+            args,
+            sink,
+            commands: NoCommands,
+            program_name,
+            table,
+            syntax,
+            stop_at_positional,
+        } = self;
+        Arguments {
+            args,
+            sink,
+            commands,
+            program_name,
+            table,
+            syntax,
+            stop_at_positional,
         }
-        Ok(())
     }
     pub fn into_values(self) -> (A::Values, S) {
         let Self {
             args,
             sink,
+            commands: _,
             program_name: _,
-            short_lut: _,
-            long_map: _,
+            table: _,
+            syntax: _,
+            stop_at_positional: _,
         } = self;
         (args.into_values(), sink)
     }
-    pub fn help_msg<'a>(&'a self) -> HelpMessage<'a, A, S> {
-        HelpMessage(self)
+}
+
+impl<'s, A: ArgumentList<'s>, S: ArgumentSink<'s>, E> Arguments<A, S, Subcommands<'s, E>> {
+    /// Returns the values of the options, the sink, and the chosen subcommand if any.
+    pub fn into_values(self) -> (A::Values, S, Option<E>) {
+        let Self {
+            args,
+            sink,
+            commands,
+            program_name: _,
+            table: _,
+            syntax: _,
+            stop_at_positional: _,
+        } = self;
+        (args.into_values(), sink, commands.into_chosen())
+    }
+    /// Returns the help message of the subcommand at `path`, such as `["remote", "add"]` for
+    /// `tool remote add`.
+    #[inline]
+    pub fn subcommand_help(&self, path: &[&str]) -> Option<impl std::fmt::Display + '_> {
+        self.commands.help_msg(path)
     }
 }
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{
+    ArgError, ArgumentList, ArgumentSink,
+    arg::Relation,
+    command::CommandSet,
+    config::{ConfigEntry, ConfigFile},
+    source::{ArgSegment, ArgSource},
+    table::OptionTable,
+};
+
+/// The parts of [crate::Arguments] used while parsing, borrowed apart from the table the source
+/// refers to.
+pub(crate) struct Parser<'p, 's, A, S, C> {
+    pub(crate) scope: Scope<'p, 's, A>,
+    pub(crate) sink: &'p mut S,
+    pub(crate) commands: &'p mut C,
+    pub(crate) stop_at_positional: bool,
+}
+
+/// The options known to a parser, falling back to the global options of the commands it's
+/// nested in.
+pub(crate) struct Scope<'p, 's, A> {
+    pub(crate) args: &'p mut A,
+    pub(crate) table: &'p OptionTable,
+    pub(crate) parent: Option<&'p mut dyn Globals<'s>>,
+}
+
+/// Captures the global options of a parent command, see [crate::Arg::global].
+pub trait Globals<'s> {
+    fn capture_global_short(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        short: char,
+    ) -> Result<bool, ArgError<'s>>;
+    fn capture_global_long(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        long: &str,
+    ) -> Result<bool, ArgError<'s>>;
+    /// Capture the global option `--{long}` from a config file, returning whether it was captured
+    /// or `None` if there is no such option.
+    ///
+    /// Options given on the command line are kept, unless the key is `repeated`.
+    fn capture_global_config(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        long: &str,
+        repeated: bool,
+    ) -> Result<Option<bool>, ArgError<'s>>;
+}
+
+impl<'s, A: ArgumentList<'s>> Globals<'s> for Scope<'_, 's, A> {
+    fn capture_global_short(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        short: char,
+    ) -> Result<bool, ArgError<'s>> {
+        if let Some(idx) = self.table.short(short)
+            && self.table.is_global(idx)
+        {
+            self.args.capture_by_index(source, idx)?;
+            return Ok(true);
+        }
+        match &mut self.parent {
+            Some(parent) => parent.capture_global_short(source, short),
+            None => Ok(false),
+        }
+    }
+    fn capture_global_long(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        long: &str,
+    ) -> Result<bool, ArgError<'s>> {
+        let found = match self.table.long(long) {
+            Some(idx) => Some((idx, false)),
+            None => self.table.long_fallback(long).ok().flatten(),
+        };
+        if let Some((idx, negated)) = found
+            && self.table.is_global(idx)
+        {
+            match negated {
+                true => source.negated(|source| self.args.capture_by_index(source, idx))?,
+                false => self.args.capture_by_index(source, idx)?,
+            }
+            return Ok(true);
+        }
+        match &mut self.parent {
+            Some(parent) => parent.capture_global_long(source, long),
+            None => Ok(false),
+        }
+    }
+    fn capture_global_config(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        long: &str,
+        repeated: bool,
+    ) -> Result<Option<bool>, ArgError<'s>> {
+        if let Some(idx) = self.table.long(long)
+            && self.table.is_global(idx)
+        {
+            return self.capture_config(source, idx, repeated).map(Some);
+        }
+        match &mut self.parent {
+            Some(parent) => parent.capture_global_config(source, long, repeated),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'s, A: ArgumentList<'s>> Scope<'_, 's, A> {
+    /// Apply the implications between arguments, then check their other relations and the
+    /// groups they belong to.
+    #[cold]
+    #[inline(never)]
+    fn check_relations(&mut self) -> Result<(), ArgError<'s>> {
+        let table = self.table;
+        let args = &mut *self.args;
+        // Implied flags may imply others in turn
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(idx, relation, implied) in table.relations() {
+                if let Relation::Implies(_) = relation
+                    && args.is_seen(idx)
+                    && !args.is_seen(implied)
+                {
+                    args.capture_by_index(&mut ArgSource::new(&[]), implied)?;
+                    changed = true;
+                }
+            }
+        }
+        for &(idx, relation, other_idx) in table.relations() {
+            let (given, other_given) = (args.is_seen(idx), args.is_seen(other_idx));
+            let error: fn(_, _) -> _ = match relation {
+                Relation::ConflictsWith(_) if given && other_given => ArgError::Conflict,
+                Relation::Requires(_) if given && !other_given => ArgError::MissingRequirement,
+                Relation::RequiredUnless(_) if !given && !other_given => ArgError::RequiredUnless,
+                _ => continue,
+            };
+            let ctx = Box::new(*args.ctx_by_index(idx));
+            return Err(error(ctx, Box::new(*args.ctx_by_index(other_idx))));
+        }
+        for group in &table.groups {
+            // Every member was added before the group
+            let members = group.members.iter().filter_map(|&long| table.long(long));
+            let given = members.clone().filter(|&idx| args.is_seen(idx)).count();
+            if !group.rule.allows(given) {
+                let members = members.map(|idx| *args.ctx_by_index(idx)).collect();
+                return Err(ArgError::GroupViolation(*group, given, members));
+            }
+        }
+        Ok(())
+    }
+    /// Capture the arguments that weren't given from the `section` of `config`, falling back to
+    /// the global options of the parent commands.
+    ///
+    /// Keys that are repeated are all captured, such as for `Vec<T>`.
+    #[cold]
+    #[inline(never)]
+    fn read_config(
+        &mut self,
+        config: &'s ConfigFile,
+        section: Option<&str>,
+    ) -> Result<(), ArgError<'s>> {
+        let mut captured = Vec::new();
+        for entry in config.entries(section) {
+            let (line, key, value) = match entry {
+                ConfigEntry::Pair(line, key, value) => (*line, key.as_str(), value),
+                ConfigEntry::Malformed(line, text) => {
+                    let err = ArgError::MalformedConfigLine(text);
+                    return Err(ArgError::InConfigFile(config.path(), *line, Box::new(err)));
+                }
+            };
+            let in_file = |err| ArgError::InConfigFile(config.path(), line, Box::new(err));
+            let repeated = captured.contains(&key);
+            let mut file = ArgSource::new(&[]);
+            file.supply_value(value);
+            let found = match (self.table.long(key), &mut self.parent) {
+                (Some(idx), _) => Some(self.capture_config(&mut file, idx, repeated)),
+                (None, Some(parent)) => parent
+                    .capture_global_config(&mut file, key, repeated)
+                    .transpose(),
+                (None, None) => None,
+            };
+            let found =
+                found.ok_or_else(|| in_file(ArgError::UnknownLongOption(OsStr::new(key))))?;
+            if found.map_err(in_file)? && !repeated {
+                captured.push(key);
+            }
+        }
+        Ok(())
+    }
+    /// Capture the argument at `idx` from a config file, unless it was given and the key isn't
+    /// `repeated`.
+    fn capture_config(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        idx: u16,
+        repeated: bool,
+    ) -> Result<bool, ArgError<'s>> {
+        if self.args.is_seen(idx) && !repeated {
+            return Ok(false);
+        }
+        self.args.capture_by_index(source, idx)?;
+        Ok(true)
+    }
+    /// Capture the arguments that weren't given from their environment variable, if set.
+    #[cold]
+    #[inline(never)]
+    fn read_env(&mut self, vars: &'s HashMap<OsString, OsString>) -> Result<(), ArgError<'s>> {
+        for &(idx, var) in self.table.envs() {
+            if self.args.is_seen(idx) {
+                continue;
+            }
+            let Some(value) = vars.get(OsStr::new(var)) else {
+                continue;
+            };
+            // Not `ArgSource::new`, values may not be valid UTF-8
+            let mut env = ArgSource::new_os(&[]);
+            env.supply_value(value.as_os_str());
+            self.args
+                .capture_by_index(&mut env, idx)
+                .map_err(|err| ArgError::InvalidEnvValue(var, Box::new(err)))?;
+        }
+        Ok(())
+    }
+    /// Capture an unknown short option with the global options of the parent commands.
+    #[cold]
+    fn parent_short(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        short: char,
+    ) -> Result<(), ArgError<'s>> {
+        let captured = match &mut self.parent {
+            Some(parent) => parent.capture_global_short(source, short)?,
+            None => false,
+        };
+        match captured {
+            true => Ok(()),
+            false => Err(ArgError::UnknownShortOption(short)),
+        }
+    }
+    /// Capture an unknown long option with the global options of the parent commands.
+    #[cold]
+    fn parent_long(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        long: &'s OsStr,
+        name: Option<&str>,
+    ) -> Result<(), ArgError<'s>> {
+        let captured = match (&mut self.parent, name) {
+            (Some(parent), Some(name)) => parent.capture_global_long(source, name)?,
+            _ => false,
+        };
+        match captured {
+            true => Ok(()),
+            false => Err(ArgError::UnknownLongOption(long)),
+        }
+    }
+}
+
+impl<'s, A: ArgumentList<'s>, S: ArgumentSink<'s>, C: CommandSet<'s>> Parser<'_, 's, A, S, C> {
+    /// Capture every remaining argument and read the environment variables and config file for
+    /// those that weren't given, then check the relations between them and that none of the
+    /// required ones are missing.
+    ///
+    /// A missing positional argument is reported on its own, missing options all at once.
+    #[inline(always)]
+    pub(crate) fn run(&mut self, source: &mut ArgSource<'_, 's>) -> Result<(), ArgError<'s>> {
+        let mut position = 0;
+        while let Some(segment) = source.next() {
+            match segment {
+                ArgSegment::Short(short) => match self.scope.table.short(short) {
+                    Some(idx) => self.scope.args.capture_by_index(source, idx)?,
+                    None => self.scope.parent_short(source, short)?,
+                },
+                ArgSegment::Long(long) => {
+                    let table = self.scope.table;
+                    let name = source.as_str(long);
+                    let found = match name.and_then(|name| table.long(name)) {
+                        Some(idx) => Some((idx, false)),
+                        None => name
+                            .map_or(Ok(None), |name| table.long_fallback(name))
+                            .map_err(|names| ArgError::AmbiguousLongOption(long, names))?,
+                    };
+                    let Some((idx, negated)) = found else {
+                        self.scope.parent_long(source, long, name)?;
+                        continue;
+                    };
+                    let args = &mut *self.scope.args;
+                    match negated {
+                        true => source.negated(|source| args.capture_by_index(source, idx))?,
+                        false => args.capture_by_index(source, idx)?,
+                    }
+                }
+                ArgSegment::Value(val) => {
+                    if let Some(command) =
+                        source.as_str(val).and_then(|name| self.commands.find(name))
+                    {
+                        // The subcommand takes every argument that's left
+                        self.commands.parse(command, source, &mut self.scope)?;
+                        break;
+                    }
+                    if self.stop_at_positional {
+                        source.terminate();
+                        self.positional(source, &mut position, val, S::consume_trailing)?;
+                    } else {
+                        self.positional(source, &mut position, val, S::consume_os_value)?;
+                    }
+                }
+                ArgSegment::Trailing(val) => {
+                    self.positional(source, &mut position, val, S::consume_trailing)?;
+                }
+            }
+        }
+        if !self.scope.table.envs().is_empty() {
+            // Only the variables are passed on, so the source can stay in registers
+            self.scope.read_env(source.env_vars())?;
+        }
+        if let Some((config, section)) = source.config() {
+            self.scope.read_config(config, section)?;
+        }
+        if self.scope.table.has_relations() {
+            self.scope.check_relations()?;
+        }
+        if !self.scope.table.has_required() {
+            return Ok(());
+        }
+        match self.scope.args.visit_missing(&mut |_| Err(())) {
+            Ok(()) => Ok(()),
+            Err(()) => Err(self.missing()),
+        }
+    }
+    /// The error for the first missing positional argument, or else every missing option.
+    #[cold]
+    fn missing(&self) -> ArgError<'s> {
+        let mut missing = Vec::new();
+        let positional = self
+            .scope
+            .args
+            .visit_missing(&mut |ctx| match ctx.positional {
+                Some(_) => Err(ArgError::MissingPositional(Box::new(*ctx))),
+                None => {
+                    missing.push(*ctx);
+                    Ok(())
+                }
+            });
+        positional
+            .err()
+            .unwrap_or(ArgError::MissingRequired(missing))
+    }
+    /// Capture `value` with the positional argument at `position`, moving on to the next one
+    /// unless it takes several values.
+    ///
+    /// Values left over once every positional argument was given go to `sink`.
+    #[inline(always)]
+    fn positional(
+        &mut self,
+        source: &mut ArgSource<'_, 's>,
+        position: &mut usize,
+        value: &'s OsStr,
+        sink: fn(&mut S, &'s OsStr) -> Result<(), ArgError<'s>>,
+    ) -> Result<(), ArgError<'s>> {
+        let Some((idx, multiple)) = self.scope.table.positional(*position) else {
+            return self.leftover(value, sink);
+        };
+        if !multiple {
+            *position += 1;
+        }
+        source.set_value(value);
+        self.scope.args.capture_by_index(source, idx)
+    }
+    /// Reject a value left over after every positional argument was given, unless the sink keeps
+    /// it.
+    #[inline(always)]
+    fn leftover(
+        &mut self,
+        value: &'s OsStr,
+        sink: fn(&mut S, &'s OsStr) -> Result<(), ArgError<'s>>,
+    ) -> Result<(), ArgError<'s>> {
+        if S::DISCARDS && !self.commands.is_empty() {
+            return Err(ArgError::UnknownSubcommand(value));
+        }
+        if S::DISCARDS && self.scope.table.has_positionals() {
+            return Err(ArgError::ExtraPositional(value));
+        }
+        sink(self.sink, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Arguments, Subcommand, Subcommands, prelude::*};
+
+    #[test]
+    fn config_sections_set_global_options() {
+        let config = ConfigFile::parse("tool.ini", "[build]\nverbose = true\n");
+        let build = Subcommand::new("build", Arguments::new(), |_| ());
+        let mut parsed = Arguments::new()
+            .add(Arg::new_flag().with_long("verbose").global())
+            .with_subcommands(Subcommands::new().add(build));
+        let source = ArgSource::new(&["build"]).with_config_file(&config);
+        parsed.parse_source(source).unwrap();
+        assert!(parsed.into_values().0);
+    }
+
+    #[test]
+    #[should_panic(expected = "No option named --output added before")]
+    fn relations_to_unknown_options_panic_when_added() {
+        let _ = Arguments::new()
+            .add(Arg::new_flag().with_long("verbose"))
+            .add(Arg::new_flag().with_long("quiet").conflicts_with("output"));
+    }
+
+    #[test]
+    #[should_panic(expected = "--output takes a value, so it can't be implied")]
+    fn implying_an_option_with_a_value_panics() {
+        let _ = Arguments::new()
+            .add(opt_none::<&str>().with_long("output"))
+            .add(Arg::new_flag().with_long("save").implies("output"));
+    }
+
+    #[test]
+    fn from_str_values_fill_collections() {
+        #[derive(Debug, PartialEq)]
+        struct Level(u8);
+        impl std::str::FromStr for Level {
+            type Err = std::num::ParseIntError;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Level)
+            }
+        }
+        let mut parsed = Arguments::new()
+            .add(opt_none::<FromStrValue<Level>>().with_long("level"))
+            .add(Arg::<Vec<FromStrValue<Level>>>::empty().with_long("levels"));
+        parsed
+            .parse(&["--level", "1", "--levels", "2", "--levels=3"])
+            .unwrap();
+        let (level, levels) = parsed.into_values().0;
+        assert_eq!(level, Some(FromStrValue(Level(1))));
+        assert_eq!(levels, [FromStrValue(Level(2)), FromStrValue(Level(3))]);
+        let mut parsed = Arguments::new().add(opt_none::<FromStrValue<Level>>().with_long("level"));
+        let err = parsed.parse(&["--level", "high"]).unwrap_err();
+        assert!(matches!(err, ArgError::InvalidValueForOpt(_, _, Some(_))));
+    }
+
+    #[test]
+    fn global_options_go_before_or_after_the_subcommand() {
+        for args in [["-v", "build", "--release"], ["build", "-v", "--release"]] {
+            let build = Arguments::new().add(Arg::new_flag().with_long("release"));
+            let build = Subcommand::new("build", build, |build| build.into_values().0);
+            let mut parsed = Arguments::new()
+                .add(Arg::new_flag().with_short(b'v').global())
+                .with_subcommands(Subcommands::new().add(build));
+            parsed.parse(&args).unwrap();
+            let (verbose, _, release) = parsed.into_values();
+            assert!(verbose, "{args:?}");
+            assert_eq!(release, Some(true), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn command_line_beats_env_beats_config_beats_default() {
+        let vars = HashMap::from(
+            [("A", "env"), ("B", "env")].map(|(var, value)| (var.into(), value.into())),
+        );
+        let config = ConfigFile::parse("tool.ini", "a = config\nb = config\nc = config\n");
+        let option = |long, var| {
+            opt_none::<&str>()
+                .with_long(long)
+                .with_env(var)
+                .with_default("default")
+        };
+        let mut parsed = Arguments::new()
+            .add(option("a", "A"))
+            .add(option("b", "B"))
+            .add(option("c", "C"))
+            .add(option("d", "D"));
+        let source = ArgSource::new(&["--a=cli"])
+            .with_env_vars(&vars)
+            .with_config_file(&config);
+        parsed.parse_source(source).unwrap();
+        let (((a, b), c), d) = parsed.into_values().0;
+        assert_eq!([a, b, c, d], ["cli", "env", "config", "default"]);
+    }
+
+    #[test]
+    fn missing_and_extra_positionals() {
+        let arguments = || {
+            Arguments::new()
+                .add(Arg::<Required<&str>>::empty().positional("input"))
+                .add(opt_none::<&str>().positional("output"))
+        };
+        let err = arguments().parse(&[]).unwrap_err();
+        assert!(matches!(err, ArgError::MissingPositional(ctx) if ctx.positional == Some("input")));
+        let err = arguments().parse(&["in", "out", "more"]).unwrap_err();
+        assert!(matches!(err, ArgError::ExtraPositional(value) if value == "more"));
+        let mut parsed = arguments();
+        parsed.parse(&["in"]).unwrap();
+        assert_eq!(parsed.into_values().0, ("in", None));
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    ffi::OsString,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+/// Read the arguments listed in a response file.
+pub(crate) fn read_response_file(path: &Path) -> Result<Vec<OsString>, Error> {
+    let contents = std::fs::read_to_string(path)?;
+    split_arguments(&contents)
+}
+
+/// Split `text` on whitespace, using shell-like quoting.
+///
+/// Single quotes keep everything up to the closing quote as-is, double quotes allow escaping `"`
+/// and `\` with a backslash, and a backslash outside of quotes escapes any character.
+pub(crate) fn split_arguments(text: &str) -> Result<Vec<OsString>, Error> {
+    let mut args = Vec::new();
+    let mut chars = text.chars();
+    let mut current: Option<String> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(current.take().map(OsString::from)),
+            '\'' => {
+                let current = current.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(unterminated()),
+                    }
+                }
+            }
+            '"' => {
+                let current = current.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => current.push(c),
+                            Some(c) => current.extend(['\\', c]),
+                            None => return Err(unterminated()),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(unterminated()),
+                    }
+                }
+            }
+            '\\' => {
+                let current = current.get_or_insert_default();
+                current.extend(chars.next());
+            }
+            c => current.get_or_insert_default().push(c),
+        }
+    }
+    args.extend(current.map(OsString::from));
+    Ok(args)
+}
+
+fn unterminated() -> Error {
+    Error::new(ErrorKind::InvalidData, "unterminated quote")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{ArgError, ArgStorage, Arguments, prelude::*};
+
+    fn split(text: &str) -> Vec<OsString> {
+        split_arguments(text).unwrap()
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(split(" a\tb\n\n c  "), ["a", "b", "c"]);
+        assert_eq!(split(""), Vec::<OsString>::new());
+        assert_eq!(split(" \n "), Vec::<OsString>::new());
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(split(r#"'a b' "c d""#), ["a b", "c d"]);
+        // Quotes only group, so they can be empty or join adjacent parts
+        assert_eq!(split(r#"'' "" x'y'"z"w"#), ["", "", "xyzw"]);
+        assert_eq!(split(r#"'"' "'""#), ["\"", "'"]);
+        assert_eq!(split("'a\nb'"), ["a\nb"]);
+    }
+
+    #[test]
+    fn escaping() {
+        // Everything is kept as-is in single quotes
+        assert_eq!(split(r"'a\b\'"), [r"a\b\"]);
+        // Only `"` and `\` are escaped in double quotes
+        assert_eq!(split(r#""a\"b\\c\n""#), [r#"a"b\c\n"#]);
+        assert_eq!(split(r"a\ b \'c \\"), ["a b", "'c", "\\"]);
+        // A trailing backslash escapes nothing
+        assert_eq!(split("a\\"), ["a"]);
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        for text in ["'a", "\"a", "\"a\\\"", "\"a\\"] {
+            let err = split_arguments(text).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{text}");
+        }
+    }
+
+    /// Write `files` to a fresh directory, returning it.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("argwack-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, text) in files {
+            std::fs::write(dir.join(name), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn nested_files() {
+        let dir = write_files("nested", &[("inner", "'-b' x")]);
+        let [outer, inner] =
+            ["outer", "inner"].map(|name| format!("@{}", dir.join(name).display()));
+        std::fs::write(dir.join("outer"), format!("-a {inner} -c")).unwrap();
+        let storage = ArgStorage::new();
+        let mut parsed = Arguments::new_with_sink(Vec::<&str>::new())
+            .add(Arg::new_flag().with_short(b'a'))
+            .add(opt_none::<&str>().with_short(b'b'))
+            .add(Arg::new_flag().with_short(b'c'));
+        // Arguments after the terminator are never expanded
+        parsed
+            .parse_with_response_files(&[&outer, "--", &inner], &storage)
+            .unwrap();
+        let (((a, b), c), rest) = parsed.into_values();
+        assert_eq!((a, b, c, rest), (true, Some("x"), true, vec![&*inner]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cycles() {
+        let dir = write_files("cycles", &[]);
+        let [a, b, c] = ["a", "b", "c"].map(|name| format!("@{}", dir.join(name).display()));
+        std::fs::write(dir.join("a"), format!("-v {b}")).unwrap();
+        std::fs::write(dir.join("b"), format!("{c} {c}")).unwrap();
+        std::fs::write(dir.join("c"), format!("-v {a}")).unwrap();
+        let storage = ArgStorage::new();
+        let mut parsed = Arguments::new().add(Arg::new_count().with_short(b'v'));
+        let err = parsed
+            .parse_with_response_files(&[&a], &storage)
+            .unwrap_err();
+        assert!(matches!(err, ArgError::ResponseFileCycle(_)), "{err}");
+        // Including the same file twice isn't a cycle
+        std::fs::write(dir.join("c"), "-v").unwrap();
+        let mut parsed = Arguments::new().add(Arg::new_count().with_short(b'v'));
+        parsed
+            .parse_with_response_files(&[&a, &c], &storage)
+            .unwrap();
+        assert_eq!(parsed.into_values().0, 4);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,88 +1,712 @@
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    ArgError,
+    arg::ArgContext,
+    config::ConfigFile,
+    env::{env_args, env_vars},
+    response::read_response_file,
+    storage::ArgStorage,
+    syntax::{ShortStyle, Syntax},
+    table::OptionTable,
+};
+
 #[derive(Debug, Clone)]
 enum Saved<'a> {
     Empty,
-    Value(&'a str),
+    Value(&'a OsStr),
+    /// A value read from elsewhere than the command line, see [ArgSource::supplied_value].
+    Supplied(&'a OsStr),
     Shorts(&'a [u8]),
 }
 
+#[derive(Debug, Clone)]
+enum Tokens<'s, 'a> {
+    Str(core::slice::Iter<'s, &'a str>),
+    Os(core::slice::Iter<'s, &'a OsStr>),
+    Owned(core::slice::Iter<'a, OsString>),
+}
+
+impl<'s, 'a> Tokens<'s, 'a> {
+    #[inline(always)]
+    fn is_utf8(&self) -> bool {
+        matches!(self, Tokens::Str(_))
+    }
+    #[inline(always)]
+    fn next(&mut self) -> Option<&'a OsStr> {
+        match self {
+            Tokens::Str(iter) => iter.next().map(|&arg| OsStr::new(arg)),
+            Tokens::Os(iter) => iter.next().copied(),
+            Tokens::Owned(iter) => iter.next().map(OsString::as_os_str),
+        }
+    }
+    #[inline(always)]
+    fn peek(&self) -> Option<&'a OsStr> {
+        match self {
+            Tokens::Str(iter) => iter.as_slice().first().map(|&arg| OsStr::new(arg)),
+            Tokens::Os(iter) => iter.as_slice().first().copied(),
+            Tokens::Owned(iter) => iter.as_slice().first().map(OsString::as_os_str),
+        }
+    }
+    #[inline(always)]
+    fn len(&self) -> usize {
+        match self {
+            Tokens::Str(iter) => iter.len(),
+            Tokens::Os(iter) => iter.len(),
+            Tokens::Owned(iter) => iter.len(),
+        }
+    }
+}
+
+/// Response files being expanded, with the innermost one last.
+#[derive(Debug, Clone)]
+struct ResponseFiles<'a> {
+    storage: &'a ArgStorage,
+    open: Vec<(PathBuf, core::slice::Iter<'a, OsString>)>,
+    /// Reading a response file failed, parsing stops here.
+    error: Option<ArgError<'a>>,
+}
+
+impl<'a> ResponseFiles<'a> {
+    fn open(&mut self, path: &'a Path) -> Result<(), ArgError<'a>> {
+        let io_error = |err| ArgError::ResponseFile(path, Arc::new(err));
+        let canonical = path.canonicalize().map_err(io_error)?;
+        if self.open.iter().any(|(open, _)| *open == canonical) {
+            return Err(ArgError::ResponseFileCycle(path));
+        }
+        let tokens = read_response_file(&canonical).map_err(io_error)?;
+        self.open
+            .push((canonical, self.storage.alloc(tokens).iter()));
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ArgSource<'s, 'a> {
-    args: core::slice::Iter<'s, &'a str>,
+    args: Tokens<'s, 'a>,
     saved: Saved<'a>,
+    /// Set once the `--` terminator has been seen, every token after it is trailing.
+    terminated: bool,
+    /// The options registered by the [crate::Arguments] being parsed.
+    table: Option<&'s OptionTable>,
+    /// Custom option syntax, [Syntax::GNU] is used if unset.
+    syntax: Option<&'s Syntax>,
+    /// Set while the negation of an option is captured.
+    negated: bool,
+    /// Set when `@file` arguments are expanded.
+    responses: Option<Box<ResponseFiles<'a>>>,
+    /// Environment variables to read instead of those of the current process.
+    env: Option<&'a HashMap<OsString, OsString>>,
+    /// Values for the options that weren't given, see [ArgSource::with_config_file].
+    config: Option<&'a ConfigFile>,
+    /// The subcommand being read, naming its section of the config file.
+    section: Option<&'static str>,
 }
 
 impl<'s, 'a> ArgSource<'s, 'a> {
     #[inline(always)]
     pub fn new(args: &'s [&'a str]) -> Self {
+        Self::from_tokens(Tokens::Str(args.iter()))
+    }
+    /// Read arguments that may not be valid UTF-8, such as paths.
+    #[inline(always)]
+    pub fn new_os(args: &'s [&'a OsStr]) -> Self {
+        Self::from_tokens(Tokens::Os(args.iter()))
+    }
+    /// Read arguments owned elsewhere, such as in an [ArgStorage].
+    #[inline(always)]
+    pub fn new_owned(args: &'a [OsString]) -> Self {
+        Self::from_tokens(Tokens::Owned(args.iter()))
+    }
+    /// Read the arguments of the current process, skipping the program name.
+    #[inline]
+    pub fn from_env() -> Self {
+        Self::new_owned(env_args().get(1..).unwrap_or_default())
+    }
+    #[inline(always)]
+    fn from_tokens(args: Tokens<'s, 'a>) -> Self {
         Self {
-            args: args.iter(),
+            args,
             saved: Saved::Empty,
+            terminated: false,
+            negated: false,
+            table: None,
+            syntax: None,
+            responses: None,
+            env: None,
+            config: None,
+            section: None,
+        }
+    }
+    /// Replace every `@file` argument with the arguments listed in `file`.
+    ///
+    /// Files are split on whitespace with shell-like quoting, and may refer to other files.
+    /// Arguments following the `--` terminator are never expanded.
+    #[inline]
+    pub fn with_response_files(mut self, storage: &'a ArgStorage) -> Self {
+        self.responses = Some(Box::new(ResponseFiles {
+            storage,
+            open: Vec::new(),
+            error: None,
+        }));
+        self
+    }
+    /// Read the environment variables of [crate::Arg::with_env] from `vars` instead of the
+    /// environment of the current process, such as in tests.
+    #[inline]
+    pub fn with_env_vars(mut self, vars: &'a HashMap<OsString, OsString>) -> Self {
+        self.env = Some(vars);
+        self
+    }
+    /// Read the options that weren't given from `config`.
+    ///
+    /// The command line and environment variables take precedence over the file.
+    #[inline]
+    pub fn with_config_file(mut self, config: &'a ConfigFile) -> Self {
+        self.config = Some(config);
+        self
+    }
+    /// Same as [ArgSource::with_config_file], reading the file named by the last `--{long}`
+    /// option, if any, kept in `storage`.
+    ///
+    /// The arguments are scanned before parsing, so `--{long}` must also be registered for the
+    /// parse to accept it. Arguments in response files aren't scanned.
+    pub fn with_config_option(
+        mut self,
+        long: &'static str,
+        storage: &'a ArgStorage,
+    ) -> Result<Self, ArgError<'a>> {
+        let mut tokens = self.args.clone();
+        let mut path = None;
+        while let Some(token) = tokens.next() {
+            let Some(name) = token.as_encoded_bytes().strip_prefix(b"--") else {
+                continue;
+            };
+            match name.strip_prefix(long.as_bytes()) {
+                _ if name.is_empty() => break,
+                Some([]) => path = tokens.next(),
+                Some([b'=', value @ ..]) => path = Some(os_str(value)),
+                _ => (),
+            }
+        }
+        if let Some(path) = path.map(Path::new) {
+            let config = ConfigFile::read(path)
+                .map_err(|err| ArgError::ConfigFileRead(path, Arc::new(err)))?;
+            self.config = Some(storage.alloc_config(config));
+        }
+        Ok(self)
+    }
+    #[inline(always)]
+    pub(crate) fn configure<'t>(
+        self,
+        table: &'t OptionTable,
+        syntax: Option<&'t Syntax>,
+    ) -> ArgSource<'t, 'a>
+    where
+        's: 't,
+    {
+        ArgSource {
+            args: self.args,
+            saved: self.saved,
+            terminated: self.terminated,
+            negated: self.negated,
+            table: Some(table),
+            syntax,
+            responses: self.responses,
+            env: self.env,
+            config: self.config,
+            section: self.section,
+        }
+    }
+    /// Read the remaining arguments with another option table and syntax, such as those of the
+    /// subcommand called `section`.
+    pub(crate) fn nested<R>(
+        &mut self,
+        section: &'static str,
+        table: &OptionTable,
+        syntax: Option<&Syntax>,
+        read: impl FnOnce(&mut ArgSource<'_, 'a>) -> R,
+    ) -> R {
+        let mut nested = ArgSource {
+            args: self.args.clone(),
+            saved: core::mem::replace(&mut self.saved, Saved::Empty),
+            terminated: self.terminated,
+            negated: false,
+            table: Some(table),
+            syntax,
+            responses: self.responses.take(),
+            env: self.env,
+            config: self.config,
+            section: Some(section),
+        };
+        let result = read(&mut nested);
+        // The nested tokens borrow the table for less time than ours do, so catch up instead
+        for _ in nested.args.len()..self.args.len() {
+            self.args.next();
+        }
+        self.saved = nested.saved;
+        self.terminated = nested.terminated;
+        self.responses = nested.responses;
+        result
+    }
+    /// Returns the environment variables to read, see [ArgSource::with_env_vars].
+    ///
+    /// The environment of the current process is read once and kept for the rest of the program.
+    #[inline]
+    pub(crate) fn env_vars(&self) -> &'a HashMap<OsString, OsString> {
+        self.env.unwrap_or_else(|| env_vars())
+    }
+    /// Returns the config file to read, and the section for the options being parsed.
+    #[inline(always)]
+    pub(crate) fn config(&self) -> Option<(&'a ConfigFile, Option<&'static str>)> {
+        Some((self.config?, self.section))
+    }
+    /// Take the error that stopped the expansion of response files.
+    #[inline(always)]
+    pub(crate) fn take_error(&mut self) -> Option<ArgError<'a>> {
+        self.responses.as_mut()?.error.take()
+    }
+    #[inline(always)]
+    fn next_token(&mut self) -> Option<&'a OsStr> {
+        if self.responses.is_none() {
+            return self.args.next();
+        }
+        let token = self.peek_expanded()?;
+        self.skip_token();
+        Some(token)
+    }
+    #[inline(always)]
+    fn peek_token(&mut self) -> Option<&'a OsStr> {
+        if self.responses.is_none() {
+            return self.args.peek();
+        }
+        self.peek_expanded()
+    }
+    fn skip_token(&mut self) {
+        match self
+            .responses
+            .as_mut()
+            .and_then(|files| files.open.last_mut())
+        {
+            Some((_, tokens)) => _ = tokens.next(),
+            None => _ = self.args.next(),
+        }
+    }
+    /// Find the next token, opening every response file in the way.
+    fn peek_expanded(&mut self) -> Option<&'a OsStr> {
+        loop {
+            let files = self.responses.as_mut()?;
+            let token = match files.open.last() {
+                Some((_, tokens)) => match tokens.as_slice().first() {
+                    Some(token) => token.as_os_str(),
+                    None => {
+                        files.open.pop();
+                        continue;
+                    }
+                },
+                None => self.args.peek()?,
+            };
+            let path = match token.as_encoded_bytes() {
+                [b'@', path @ ..] if !path.is_empty() && !self.terminated => {
+                    Path::new(os_str(path))
+                }
+                _ => return Some(token),
+            };
+            self.skip_token();
+            let files = self.responses.as_mut()?;
+            if let Err(err) = files.open(path) {
+                files.error = Some(err);
+                return None;
+            }
         }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum ArgSegment<'s> {
-    Short(u8),
-    Long(&'s str),
-    Value(&'s str),
+    Short(char),
+    /// The name of a long option, not necessarily valid UTF-8.
+    Long(&'s OsStr),
+    Value(&'s OsStr),
+    /// A token following the `--` terminator, passed on verbatim.
+    Trailing(&'s OsStr),
+}
+
+/// Reinterpret bytes split off an [OsStr] right before or after an ASCII character, or right after
+/// a valid UTF-8 character.
+#[inline(always)]
+fn os_str(bytes: &[u8]) -> &OsStr {
+    // SAFETY: Callers only split encoded bytes on the boundaries listed above
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
+
+/// Split `value` on every ASCII `delimiter`.
+#[inline]
+pub(crate) fn split_os(value: &OsStr, delimiter: u8) -> impl Iterator<Item = &OsStr> {
+    value
+        .as_encoded_bytes()
+        .split(move |&byte| byte == delimiter)
+        .map(os_str)
+}
+
+/// Split `value` on the first ASCII `delimiter`.
+#[inline]
+pub(crate) fn split_os_once(value: &OsStr, delimiter: u8) -> Option<(&OsStr, &OsStr)> {
+    let bytes = value.as_encoded_bytes();
+    let at = memchr::memchr(delimiter, bytes)?;
+    Some((os_str(&bytes[..at]), os_str(&bytes[at + 1..])))
+}
+
+/// Split the first short option off a non-empty cluster of shorts.
+///
+/// Yields no remainder if the cluster doesn't start with a valid UTF-8 character.
+#[inline(always)]
+fn split_short(shorts: &[u8]) -> (char, Option<&[u8]>) {
+    match shorts {
+        [first, rest @ ..] if first.is_ascii() => (*first as char, Some(rest)),
+        _ => split_unicode_short(shorts),
+    }
+}
+
+#[cold]
+fn split_unicode_short(shorts: &[u8]) -> (char, Option<&[u8]>) {
+    let len = shorts[0].leading_ones() as usize;
+    let short = shorts
+        .get(..len)
+        .filter(|_| (2..=4).contains(&len))
+        .and_then(|bytes| core::str::from_utf8(bytes).ok())
+        .and_then(|short| short.chars().next());
+    match short {
+        Some(short) => (short, Some(&shorts[len..])),
+        None => (char::REPLACEMENT_CHARACTER, None),
+    }
 }
 
 impl<'s, 'a> ArgSource<'s, 'a> {
+    /// Take the value for the option described by `ctx`, either attached to it or the next token.
+    ///
+    /// Tokens starting with `-` are only taken if `ctx` allows hyphen values, if they are a lone
+    /// `-`, or if they look like a negative number and don't name a registered short option.
     #[inline(always)]
-    pub fn next_value(&mut self) -> Option<&'a str> {
+    pub fn next_value(&mut self, ctx: &ArgContext) -> Option<&'a OsStr> {
+        if let Some(value) = self.attached_value() {
+            return Some(value);
+        }
+        self.peek_detached_value(ctx)?;
+        self.next_token()
+    }
+    /// Returns the value [ArgSource::next_value] would take, without taking it.
+    #[inline(always)]
+    pub fn peek_value(&mut self, ctx: &ArgContext) -> Option<&'a OsStr> {
         match self.saved {
-            Saved::Empty | Saved::Shorts([]) => (),
-            Saved::Value(val) => {
-                self.saved = Saved::Empty;
-                return Some(val);
-            }
-            Saved::Shorts(looks_like_a_value) => {
-                self.saved = Saved::Empty;
-                return Some(core::str::from_utf8(looks_like_a_value).unwrap());
-            }
+            Saved::Value(val) | Saved::Supplied(val) => Some(val),
+            Saved::Shorts(rest) if !rest.is_empty() => Some(os_str(rest)),
+            Saved::Empty | Saved::Shorts(_) => self.peek_detached_value(ctx),
         }
-        let first = self.args.next()?;
-        if first.starts_with('-') {
+    }
+    #[inline(always)]
+    fn peek_detached_value(&mut self, ctx: &ArgContext) -> Option<&'a OsStr> {
+        let first = self.peek_token()?;
+        let option_name = match self.syntax {
+            None => first.as_encoded_bytes().strip_prefix(b"-"),
+            Some(syntax) => syntax.option_name(first.as_encoded_bytes()),
+        };
+        if let Some(name) = option_name
+            && !name.is_empty()
+            && !ctx.allow_hyphen_values
+            && !self.is_negative_number(first.as_encoded_bytes())
+        {
             return None;
         }
         Some(first)
     }
+    /// Take the value attached to the current option, as in `--name=value` or `-nvalue`.
+    ///
+    /// Unlike [ArgSource::next_value], the next token is never taken, so `--name value` has no
+    /// attached value.
+    #[inline(always)]
+    pub fn attached_value(&mut self) -> Option<&'a OsStr> {
+        let value = match self.saved {
+            Saved::Value(val) | Saved::Supplied(val) => val,
+            Saved::Shorts(rest) if !rest.is_empty() => os_str(rest),
+            Saved::Empty | Saved::Shorts(_) => return None,
+        };
+        self.saved = Saved::Empty;
+        Some(value)
+    }
+    /// Checks if a token is a negative number, rather than a cluster of shorts.
+    #[cold]
+    fn is_negative_number(&self, token: &[u8]) -> bool {
+        let [b'-', rest @ ..] = token else {
+            return false;
+        };
+        let looks_numeric = match rest {
+            [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..] => {
+                core::str::from_utf8(rest).is_ok_and(|number| number.parse::<f64>().is_ok())
+            }
+            _ => false,
+        };
+        let registered = self
+            .table
+            .is_some_and(|table| table.short(rest[0] as char).is_some());
+        looks_numeric && !registered
+    }
+    /// Convert a value handed out by this source to a [str].
+    ///
+    /// Skips UTF-8 validation when the source was created from `&str`s.
+    #[inline(always)]
+    pub fn as_str(&self, value: &'a OsStr) -> Option<&'a str> {
+        if self.args.is_utf8() {
+            // SAFETY: Every value is a `&str`, a slice of one split next to an ASCII character, or
+            // read from a response or config file as a `String`, see `set_value`
+            return Some(unsafe { core::str::from_utf8_unchecked(value.as_encoded_bytes()) });
+        }
+        value.to_str()
+    }
+    /// Returns true once the `--` terminator has been consumed.
+    #[inline(always)]
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+    /// Hand out `value` as the value of the next argument captured, as if it was attached to it.
+    ///
+    /// `value` must be valid UTF-8 if this source was created from `&str`s, see
+    /// [ArgSource::as_str].
+    #[inline(always)]
+    pub(crate) fn set_value(&mut self, value: &'a OsStr) {
+        self.saved = Saved::Value(value);
+    }
+    /// Like [ArgSource::set_value], for a value read from an environment variable or config file.
+    #[inline(always)]
+    pub(crate) fn supply_value(&mut self, value: &'a OsStr) {
+        self.saved = Saved::Supplied(value);
+    }
+    /// Take the value read from an environment variable or config file for an option that
+    /// usually takes none, such as a flag.
+    ///
+    /// Values given on the command line are never taken, so `-vq` stays two flags.
+    #[inline(always)]
+    pub fn supplied_value(&mut self) -> Option<&'a OsStr> {
+        match self.saved {
+            Saved::Supplied(val) => {
+                self.saved = Saved::Empty;
+                Some(val)
+            }
+            _ => None,
+        }
+    }
+    /// Pass every remaining argument on verbatim, as if a `--` terminator was consumed.
+    #[inline(always)]
+    pub(crate) fn terminate(&mut self) {
+        self.terminated = true;
+    }
+    /// Returns true if the option being captured was given in its negated form, such as
+    /// `--no-name`.
+    #[inline(always)]
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+    /// Run `capture` as if the option was given in its negated form.
+    #[cold]
+    pub(crate) fn negated<R>(&mut self, capture: impl FnOnce(&mut Self) -> R) -> R {
+        self.negated = true;
+        let result = capture(self);
+        self.negated = false;
+        result
+    }
     #[inline(always)]
     pub fn next(&mut self) -> Option<ArgSegment<'a>> {
         match self.saved {
             Saved::Empty | Saved::Shorts([]) => (),
-            Saved::Value(val) => {
+            Saved::Value(val) | Saved::Supplied(val) => {
                 self.saved = Saved::Empty;
                 return Some(ArgSegment::Value(val));
             }
-            Saved::Shorts([first, rest @ ..]) => {
-                self.saved = Saved::Shorts(rest);
-                return Some(ArgSegment::Short(*first));
+            Saved::Shorts(shorts) => {
+                let (short, rest) = split_short(shorts);
+                self.saved = rest.map_or(Saved::Empty, Saved::Shorts);
+                return Some(ArgSegment::Short(short));
             }
         }
-        let first = self.args.next()?;
-        match first.as_bytes() {
+        let first = self.next_token()?;
+        if self.terminated {
+            return Some(ArgSegment::Trailing(first));
+        }
+        if let Some(syntax) = self.syntax {
+            return self.segment_with(syntax, first);
+        }
+        match first.as_encoded_bytes() {
+            [b'-', b'-'] => {
+                self.terminated = true;
+                let first = self.next_token()?;
+                Some(ArgSegment::Trailing(first))
+            }
             [b'-', b'-', name @ ..] => {
                 let mut name = name;
                 if let Some(eq) = memchr::memchr(b'=', name) {
-                    self.saved = Saved::Value(&first[2 + eq + 1..]);
+                    self.saved = Saved::Value(os_str(&name[eq + 1..]));
                     name = &name[..eq];
                 }
-                Some(ArgSegment::Long(unsafe {
-                    core::str::from_utf8_unchecked(name)
-                }))
+                Some(ArgSegment::Long(os_str(name)))
             }
-            [b'-', short_name] => Some(ArgSegment::Short(*short_name)),
-            [b'-', short_name, b'=', val @ ..] => {
-                self.saved = Saved::Value(unsafe { core::str::from_utf8_unchecked(val) });
-                Some(ArgSegment::Short(*short_name))
-            }
-            [b'-', short_name, more_shorts @ ..] => {
-                self.saved = Saved::Shorts(more_shorts);
-                Some(ArgSegment::Short(*short_name))
+            // A lone short option needs neither decoding nor saving the rest
+            [b'-', short] if short.is_ascii() => Some(ArgSegment::Short(*short as char)),
+            [b'-', shorts @ ..] if !shorts.is_empty() => {
+                let (short, rest) = split_short(shorts);
+                self.saved = match rest {
+                    Some([b'=', val @ ..]) => Saved::Value(os_str(val)),
+                    Some(more_shorts) => Saved::Shorts(more_shorts),
+                    None => Saved::Empty,
+                };
+                Some(ArgSegment::Short(short))
             }
             _ => Some(ArgSegment::Value(first)),
         }
     }
+    /// Split `token` into a segment according to a custom [Syntax].
+    fn segment_with(&mut self, syntax: &Syntax, token: &'a OsStr) -> Option<ArgSegment<'a>> {
+        let bytes = token.as_encoded_bytes();
+        if syntax.terminator.is_some_and(|end| bytes == end.as_bytes()) {
+            self.terminated = true;
+            return self.next_token().map(ArgSegment::Trailing);
+        }
+        let separated = |name: &'a [u8]| match memchr::memchr(syntax.separator, name) {
+            Some(sep) => (&name[..sep], Some(&name[sep + 1..])),
+            None => (name, None),
+        };
+        let long = |saved: &mut Saved<'a>, name: &'a [u8]| {
+            let (name, value) = separated(name);
+            if let Some(value) = value {
+                *saved = Saved::Value(os_str(value));
+            }
+            Some(ArgSegment::Long(os_str(name)))
+        };
+        if let Some(name) = bytes.strip_prefix(syntax.long_prefix.as_bytes())
+            && !name.is_empty()
+        {
+            return long(&mut self.saved, name);
+        }
+        if let Some(prefix) = syntax.short_prefix
+            && let Some(shorts) = bytes.strip_prefix(prefix.as_bytes())
+            && !shorts.is_empty()
+        {
+            if syntax.short_style == ShortStyle::Long {
+                return long(&mut self.saved, shorts);
+            }
+            let (short, rest) = split_short(shorts);
+            self.saved = match rest {
+                Some([sep, val @ ..]) if *sep == syntax.separator => Saved::Value(os_str(val)),
+                Some(more_shorts) => Saved::Shorts(more_shorts),
+                None => Saved::Empty,
+            };
+            return Some(ArgSegment::Short(short));
+        }
+        if let Some(prefix) = syntax.toggle_prefix
+            && bytes.len() > prefix.len()
+            && bytes.starts_with(prefix.as_bytes())
+        {
+            return long(&mut self.saved, bytes);
+        }
+        Some(ArgSegment::Value(token))
+    }
+}
+
+// Non-UTF-8 arguments are built from raw bytes
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{collections::HashMap, ffi::OsString, os::unix::ffi::OsStrExt};
+
+    use super::*;
+    use crate::{ArgError, Arguments, prelude::*};
+
+    fn bytes(bytes: &[u8]) -> &OsStr {
+        OsStr::from_bytes(bytes)
+    }
+
+    #[test]
+    fn split_os_keeps_invalid_utf8() {
+        let parts: Vec<_> = split_os(bytes(b"a\xff,\xfe,"), b',').collect();
+        assert_eq!(parts, [bytes(b"a\xff"), bytes(b"\xfe"), bytes(b"")]);
+        let (key, value) = split_os_once(bytes(b"\xffk=v\xfe=x"), b'=').unwrap();
+        assert_eq!((key, value), (bytes(b"\xffk"), bytes(b"v\xfe=x")));
+    }
+
+    #[test]
+    fn as_str_validates_unless_every_value_is_a_str() {
+        let value = bytes(b"a\xffb");
+        assert_eq!(ArgSource::new_os(&[]).as_str(value), None);
+        assert_eq!(ArgSource::new_owned(&[]).as_str(value), None);
+        assert_eq!(ArgSource::new(&[]).as_str(OsStr::new("é")), Some("é"));
+    }
+
+    #[test]
+    fn non_utf8_values_are_passed_on_untouched() {
+        let args = [
+            OsStr::new("--path"),
+            bytes(b"/tmp/\xff"),
+            bytes(b"--name=\xfe"),
+        ];
+        let mut parsed = Arguments::new()
+            .add(opt_none::<&OsStr>().with_long("path"))
+            .add(opt_none::<&str>().with_long("name"));
+        let err = parsed.parse_os(&args).unwrap_err();
+        assert!(
+            matches!(err, ArgError::InvalidValueForOpt(_, value, None) if value == bytes(b"\xfe"))
+        );
+        let mut parsed = Arguments::new().add(opt_none::<&OsStr>().with_long("path"));
+        parsed.parse_os(&args[..2]).unwrap();
+        assert_eq!(parsed.into_values().0, Some(bytes(b"/tmp/\xff")));
+    }
+
+    #[test]
+    fn env_values_are_validated_for_str_sources() {
+        let mut vars = HashMap::new();
+        vars.insert(OsString::from("NAME"), bytes(b"\xff\xfea").to_owned());
+        let mut parsed =
+            Arguments::new().add(opt_none::<&str>().with_long("name").with_env("NAME"));
+        let err = parsed
+            .parse_source(ArgSource::new(&[]).with_env_vars(&vars))
+            .unwrap_err();
+        assert!(matches!(err, ArgError::InvalidEnvValue("NAME", _)));
+    }
+
+    #[test]
+    fn split_short_handles_multibyte_characters() {
+        assert_eq!(split_short(b"v\xc3\xa9"), ('v', Some(&b"\xc3\xa9"[..])));
+        assert_eq!(split_short("éq".as_bytes()), ('é', Some(&b"q"[..])));
+        assert_eq!(split_short("😀".as_bytes()), ('😀', Some(&b""[..])));
+    }
+
+    #[test]
+    fn split_short_stops_at_broken_characters() {
+        // Cut mid-codepoint, a lone continuation byte, and bytes that are never valid
+        for cluster in [&b"\xc3"[..], b"\xe2\x82", b"\xa9v", b"\xffv", b"\xc3v"] {
+            assert_eq!(split_short(cluster), (char::REPLACEMENT_CHARACTER, None));
+        }
+    }
+
+    #[test]
+    fn unicode_short_clusters() {
+        let mut parsed = Arguments::new()
+            .add(Arg::new_flag().with_short('é'))
+            .add(Arg::new_flag().with_short(b'q'))
+            .add(opt_none::<&str>().with_short('ß'));
+        parsed.parse(&["-qéßwert"]).unwrap();
+        assert_eq!(parsed.into_values().0, ((true, true), Some("wert")));
+    }
+
+    #[test]
+    fn broken_short_clusters_are_unknown_options() {
+        let mut parsed = Arguments::new().add(Arg::new_flag().with_short(b'q'));
+        let err = parsed.parse_os(&[bytes(b"-q\xc3")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ArgError::UnknownShortOption(char::REPLACEMENT_CHARACTER)
+        ));
+    }
 }
@@ -0,0 +1,37 @@
+use std::{cell::RefCell, ffi::OsString};
+
+use crate::config::ConfigFile;
+
+/// Owns arguments that don't come from the caller's slice, such as the contents of response and
+/// config files, for as long as the values parsed from them are borrowed.
+#[derive(Debug, Default)]
+pub struct ArgStorage {
+    lists: RefCell<Vec<Box<[OsString]>>>,
+    // Boxed so references stay valid when the Vec grows
+    #[expect(clippy::vec_box)]
+    configs: RefCell<Vec<Box<ConfigFile>>>,
+}
+
+impl ArgStorage {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Move `tokens` into the storage, returning a reference that lives as long as it does.
+    pub(crate) fn alloc(&self, tokens: Vec<OsString>) -> &[OsString] {
+        let tokens = tokens.into_boxed_slice();
+        let ptr: *const [OsString] = &*tokens;
+        self.lists.borrow_mut().push(tokens);
+        // SAFETY: Lists are never removed or mutated until the storage is dropped, and moving the
+        // box into the Vec doesn't move its contents
+        unsafe { &*ptr }
+    }
+    /// Move `config` into the storage, returning a reference that lives as long as it does.
+    pub(crate) fn alloc_config(&self, config: ConfigFile) -> &ConfigFile {
+        let config = Box::new(config);
+        let ptr: *const ConfigFile = &*config;
+        self.configs.borrow_mut().push(config);
+        // SAFETY: Same as above
+        unsafe { &*ptr }
+    }
+}
@@ -0,0 +1,150 @@
+/// How words starting with the [short prefix](Syntax::with_short_prefix) are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShortStyle {
+    /// `-abc` is the cluster of short options `a`, `b` and `c`.
+    Clustered,
+    /// `-name` is the long option `name`, as used by `find`.
+    Long,
+}
+
+/// The prefixes and separators options are written with.
+///
+/// Every prefix is matched against the start of a token, and an option name ends at the first
+/// separator, with the rest of the token being its attached value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Syntax {
+    /// Prefix of long options, such as `--name`.
+    pub(crate) long_prefix: &'static str,
+    /// Prefix of short options, such as `-n`.
+    pub(crate) short_prefix: Option<&'static str>,
+    pub(crate) short_style: ShortStyle,
+    /// Prefix of toggles, such as `+x`.
+    pub(crate) toggle_prefix: Option<&'static str>,
+    /// ASCII character separating an option name from its attached value.
+    // NOTE: Tokens are split on it without validation, so it must never be a UTF-8 code unit
+    pub(crate) separator: u8,
+    /// Token after which every other token is passed on verbatim.
+    pub(crate) terminator: Option<&'static str>,
+}
+
+impl Syntax {
+    /// `--long=value`, `-abc`, `-s=value` and `--`.
+    pub const GNU: Self = Self::new("--", '=')
+        .with_short_prefix("-", ShortStyle::Clustered)
+        .with_terminator("--");
+    /// [Syntax::GNU], with single-dash words such as `-name` read as long options.
+    pub const SINGLE_DASH_LONG: Self = Self::GNU.with_short_prefix("-", ShortStyle::Long);
+    /// [Syntax::GNU], with `+x` toggles.
+    pub const TOGGLES: Self = Self::GNU.with_toggle_prefix("+");
+    /// DOS-style `/name:value`, without short options or a terminator.
+    pub const DOS: Self = Self::new("/", ':');
+
+    /// Long options written `{long_prefix}name`, with values attached after `separator`.
+    ///
+    /// There are no short options, toggles or terminator until they are added.
+    ///
+    /// # Panics
+    /// If `separator` isn't ASCII.
+    #[inline]
+    pub const fn new(long_prefix: &'static str, separator: char) -> Self {
+        assert!(separator.is_ascii(), "Separators must be ASCII");
+        Self {
+            long_prefix,
+            short_prefix: None,
+            short_style: ShortStyle::Clustered,
+            toggle_prefix: None,
+            separator: separator as u8,
+            terminator: None,
+        }
+    }
+    /// Read words starting with `prefix` as short options, in the given `style`.
+    #[inline]
+    pub const fn with_short_prefix(mut self, prefix: &'static str, style: ShortStyle) -> Self {
+        self.short_prefix = Some(prefix);
+        self.short_style = style;
+        self
+    }
+    /// Read words starting with `prefix` as toggles, such as `+x`.
+    ///
+    /// Toggles are looked up as long options including the prefix, so `+x` is captured by
+    /// `with_long("+x")`.
+    #[inline]
+    pub const fn with_toggle_prefix(mut self, prefix: &'static str) -> Self {
+        self.toggle_prefix = Some(prefix);
+        self
+    }
+    /// Pass every token after `terminator` on verbatim.
+    #[inline]
+    pub const fn with_terminator(mut self, terminator: &'static str) -> Self {
+        self.terminator = Some(terminator);
+        self
+    }
+    /// Returns the prefix of long options.
+    #[inline]
+    pub const fn long_prefix(&self) -> &'static str {
+        self.long_prefix
+    }
+    /// Returns the prefix of short options, if there are any.
+    #[inline]
+    pub const fn short_prefix(&self) -> Option<&'static str> {
+        self.short_prefix
+    }
+    /// Returns how words starting with the short prefix are read.
+    #[inline]
+    pub const fn short_style(&self) -> ShortStyle {
+        self.short_style
+    }
+    /// Returns the prefix of toggles, if there are any.
+    #[inline]
+    pub const fn toggle_prefix(&self) -> Option<&'static str> {
+        self.toggle_prefix
+    }
+    /// Returns the character separating an option name from its attached value.
+    #[inline]
+    pub const fn separator(&self) -> char {
+        self.separator as char
+    }
+    /// Returns the token after which every other token is passed on verbatim, if there is one.
+    #[inline]
+    pub const fn terminator(&self) -> Option<&'static str> {
+        self.terminator
+    }
+
+    /// Write the long option `long` as it's typed, such as `--name`, `/name` or `+x`.
+    pub(crate) fn write_long(
+        &self,
+        long: &str,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self.toggle_prefix {
+            Some(prefix) if long.starts_with(prefix) => f.write_str(long),
+            _ => write!(f, "{}{long}", self.long_prefix),
+        }
+    }
+    /// Returns the prefix short options are typed with, unless they can't be typed at all.
+    #[inline]
+    pub(crate) fn short_option_prefix(&self) -> Option<&'static str> {
+        self.short_prefix
+            .filter(|_| self.short_style == ShortStyle::Clustered)
+    }
+    /// Returns the name following the prefix if `token` is an option.
+    #[inline]
+    pub(crate) fn option_name<'t>(&self, token: &'t [u8]) -> Option<&'t [u8]> {
+        let prefixes = [
+            Some(self.long_prefix),
+            self.short_prefix,
+            self.toggle_prefix,
+        ];
+        prefixes.into_iter().flatten().find_map(|prefix| {
+            let name = token.strip_prefix(prefix.as_bytes())?;
+            (!name.is_empty()).then_some(name)
+        })
+    }
+}
+
+impl Default for Syntax {
+    #[inline]
+    fn default() -> Self {
+        Self::GNU
+    }
+}
@@ -0,0 +1,258 @@
+use std::borrow::Cow;
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    arg::{ArgContext, Negation, Relation},
+    group::ArgGroup,
+};
+
+/// Maps option names to the index of the [crate::Arg] that captures them.
+#[derive(Debug, Clone)]
+pub(crate) struct OptionTable {
+    // NOTE: Only ASCII shorthands live in the table, the rest go through short_map
+    short_lut: [u16; 128],
+    short_map: FxHashMap<char, u16>,
+    long_map: FxHashMap<&'static str, u16>,
+    /// Custom names of negated options.
+    negation_map: FxHashMap<&'static str, u16>,
+    /// Long options that can be negated with a `no-` prefix.
+    prefixed_negations: FxHashMap<&'static str, u16>,
+    /// Positional arguments in the order they were added, and whether they take several values.
+    positionals: Vec<(u16, bool)>,
+    /// Arguments read from an environment variable when missing, see [crate::Arg::with_env].
+    envs: Vec<(u16, &'static str)>,
+    /// Relations of the argument at the first index to the option at the second, see
+    /// [crate::Arg::conflicts_with].
+    relations: Vec<(u16, Relation, u16)>,
+    /// Groups of options checked once parsing is done, see [OptionTable::add_group].
+    pub(crate) groups: Vec<ArgGroup>,
+    /// Whether the argument at each index is global, see [crate::Arg::global].
+    global: Vec<bool>,
+    /// Whether the argument at each index takes no value, see [crate::ArgumentValue::FLAG].
+    flag: Vec<bool>,
+    /// Whether any argument is required, so missing ones are looked for once parsing is done.
+    required: bool,
+    /// Global options of the commands this one is nested in, for the help message.
+    pub(crate) inherited: Vec<ArgContext>,
+    /// Defaults shown in the help message by the names of their argument, see
+    /// [crate::Arg::with_default].
+    defaults: Vec<(ArgContext, Cow<'static, str>)>,
+    /// Resolve unambiguous prefixes of long options, as getopt_long does.
+    pub(crate) abbreviations: bool,
+}
+
+impl OptionTable {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self {
+            short_lut: [u16::MAX; _],
+            short_map: FxHashMap::default(),
+            long_map: FxHashMap::default(),
+            negation_map: FxHashMap::default(),
+            prefixed_negations: FxHashMap::default(),
+            positionals: Vec::new(),
+            envs: Vec::new(),
+            relations: Vec::new(),
+            groups: Vec::new(),
+            global: Vec::new(),
+            flag: Vec::new(),
+            required: false,
+            inherited: Vec::new(),
+            defaults: Vec::new(),
+            abbreviations: false,
+        }
+    }
+    /// Register a new argument at index 0, moving all existing ones back by one.
+    ///
+    /// Panics if a relation names an option that wasn't added before, or implies one taking a
+    /// value.
+    pub(crate) fn add(
+        &mut self,
+        ctx: &ArgContext,
+        relations: Vec<Relation>,
+        default: Option<Cow<'static, str>>,
+    ) {
+        self.short_lut
+            .iter_mut()
+            .filter(|&&mut idx| idx != u16::MAX)
+            .for_each(|idx| *idx += 1);
+        self.short_map.values_mut().for_each(|idx| *idx += 1);
+        self.long_map.values_mut().for_each(|idx| *idx += 1);
+        self.negation_map.values_mut().for_each(|idx| *idx += 1);
+        self.prefixed_negations
+            .values_mut()
+            .for_each(|idx| *idx += 1);
+        self.positionals.iter_mut().for_each(|(idx, _)| *idx += 1);
+        self.envs.iter_mut().for_each(|(idx, _)| *idx += 1);
+        self.relations.iter_mut().for_each(|(idx, _, other)| {
+            *idx += 1;
+            *other += 1;
+        });
+        if let Some(short) = ctx.short {
+            if short.is_ascii() {
+                self.short_lut[short as usize] = 0;
+            } else {
+                self.short_map.insert(short, 0);
+            }
+        }
+        if let Some(long) = ctx.long {
+            self.long_map.insert(long, 0);
+        }
+        match (ctx.negation, ctx.long) {
+            (Some(Negation::Named(negation)), _) => _ = self.negation_map.insert(negation, 0),
+            (Some(Negation::Prefixed), Some(long)) => _ = self.prefixed_negations.insert(long, 0),
+            _ => (),
+        }
+        if ctx.positional.is_some() {
+            self.positionals.push((0, ctx.multiple));
+        }
+        if let Some(env) = ctx.env {
+            self.envs.push((0, env));
+        }
+        self.global.insert(0, ctx.global);
+        self.flag.insert(0, ctx.flag);
+        for relation in relations {
+            let long = relation.long();
+            let other = self.long(long).unwrap_or_else(|| {
+                panic!("No option named --{long} added before {ctx}, which refers to it")
+            });
+            if let Relation::Implies(_) = relation {
+                assert!(
+                    self.flag[other as usize],
+                    "--{long} takes a value, so it can't be implied by {ctx}"
+                );
+            }
+            self.relations.push((0, relation, other));
+        }
+        self.required |= ctx.required;
+        self.defaults.extend(default.map(|default| (*ctx, default)));
+    }
+    /// Check how many members of `group` are given once parsing is done.
+    ///
+    /// Panics if a member wasn't added before.
+    pub(crate) fn add_group(&mut self, group: ArgGroup) {
+        for long in group.members {
+            assert!(
+                self.long_map.contains_key(long),
+                "No option named --{long} added before group {}",
+                group.name
+            );
+        }
+        self.groups.push(group);
+    }
+    /// Show a global option of a parent command in the help message.
+    pub(crate) fn inherit(&mut self, ctx: &ArgContext, default: Option<&str>) {
+        self.inherited.push(*ctx);
+        self.defaults
+            .extend(default.map(|default| (*ctx, Cow::Owned(default.to_owned()))));
+    }
+    /// Returns the default shown for the argument described by `ctx`.
+    pub(crate) fn default(&self, ctx: &ArgContext) -> Option<&str> {
+        self.defaults
+            .iter()
+            .find(|(arg, _)| arg.names() == ctx.names())
+            .map(|(_, default)| &**default)
+    }
+    /// Returns the index of the positional argument at `position`, and whether it takes several
+    /// values.
+    #[inline(always)]
+    pub(crate) fn positional(&self, position: usize) -> Option<(u16, bool)> {
+        self.positionals.get(position).copied()
+    }
+    /// Returns the index of every argument read from an environment variable, and its name.
+    #[inline(always)]
+    pub(crate) fn envs(&self) -> &[(u16, &'static str)] {
+        &self.envs
+    }
+    /// Returns the index of every argument related to another option, the relation, and the
+    /// index of the other option.
+    #[inline(always)]
+    pub(crate) fn relations(&self) -> &[(u16, Relation, u16)] {
+        &self.relations
+    }
+    /// Returns whether there are relations or groups to check once parsing is done.
+    #[inline(always)]
+    pub(crate) fn has_relations(&self) -> bool {
+        !self.relations.is_empty() || !self.groups.is_empty()
+    }
+    /// Returns whether any argument is required.
+    #[inline(always)]
+    pub(crate) fn has_required(&self) -> bool {
+        self.required
+    }
+    /// Returns whether the option with the long name `long` belongs to a group.
+    #[inline]
+    pub(crate) fn is_grouped(&self, long: Option<&str>) -> bool {
+        self.groups.iter().any(|group| group.contains(long))
+    }
+    #[inline(always)]
+    pub(crate) fn is_global(&self, idx: u16) -> bool {
+        self.global[idx as usize]
+    }
+    #[inline(always)]
+    pub(crate) fn has_positionals(&self) -> bool {
+        !self.positionals.is_empty()
+    }
+    #[inline(always)]
+    pub(crate) fn short(&self, short: char) -> Option<u16> {
+        if let Some(&idx) = self.short_lut.get(short as usize) {
+            return (idx != u16::MAX).then_some(idx);
+        }
+        self.unicode_short(short)
+    }
+    #[cold]
+    fn unicode_short(&self, short: char) -> Option<u16> {
+        self.short_map.get(&short).copied()
+    }
+    #[inline(always)]
+    pub(crate) fn long(&self, long: &str) -> Option<u16> {
+        self.long_map.get(long).copied()
+    }
+    /// Find the option named by `long` if it isn't an exact match, returning its index and whether
+    /// it was negated.
+    ///
+    /// Negations are tried before abbreviations, returning every candidate if an abbreviation is
+    /// ambiguous.
+    #[cold]
+    pub(crate) fn long_fallback(
+        &self,
+        long: &str,
+    ) -> Result<Option<(u16, bool)>, Vec<&'static str>> {
+        let negated = self.negation_map.get(long).or_else(|| {
+            let positive = long.strip_prefix("no-")?;
+            self.prefixed_negations.get(positive)
+        });
+        if let Some(&idx) = negated {
+            return Ok(Some((idx, true)));
+        }
+        Ok(self.long_abbreviation(long)?.map(|idx| (idx, false)))
+    }
+    /// Find the only long option starting with `prefix`, if abbreviations are allowed.
+    ///
+    /// Returns every candidate if there is more than one.
+    fn long_abbreviation(&self, prefix: &str) -> Result<Option<u16>, Vec<&'static str>> {
+        // An empty prefix, as in `--=value`, would match every option
+        if !self.abbreviations || prefix.is_empty() {
+            return Ok(None);
+        }
+        let mut candidates = self
+            .long_map
+            .iter()
+            .filter(|(long, _)| long.starts_with(prefix));
+        let Some((_, &idx)) = candidates.next() else {
+            return Ok(None);
+        };
+        if candidates.next().is_none() {
+            return Ok(Some(idx));
+        }
+        let mut names: Vec<_> = self
+            .long_map
+            .keys()
+            .copied()
+            .filter(|long| long.starts_with(prefix))
+            .collect();
+        names.sort_unstable();
+        Err(names)
+    }
+}
@@ -0,0 +1,133 @@
+/// A value given as one of a fixed list of strings, such as `--format json`.
+///
+/// Usually implemented by [value_enum!](crate::value_enum), hand-written implementations use
+/// `value_enum!(impl Type)` to implement [FromArgValue](crate::FromArgValue) too.
+pub trait ValueEnum: Sized {
+    /// Every accepted value, in the order of [ValueEnum::from_index].
+    const POSSIBLE_VALUES: &'static [PossibleValue];
+    /// Returns the variant for the possible value at `index`.
+    fn from_index(index: usize) -> Self;
+    /// Returns the variant named `name` or one of its aliases.
+    #[inline]
+    fn from_name(name: &str) -> Option<Self> {
+        Self::POSSIBLE_VALUES
+            .iter()
+            .position(|value| value.matches(name))
+            .map(Self::from_index)
+    }
+}
+
+/// One of the strings accepted by a [ValueEnum].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PossibleValue {
+    /// Shown in the help message and errors.
+    pub name: &'static str,
+    /// Also accepted, but never shown.
+    pub aliases: &'static [&'static str],
+    /// Listed below the option in the help message, along with the other values.
+    pub help: Option<&'static str>,
+}
+
+impl PossibleValue {
+    #[inline(always)]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            aliases: &[],
+            help: None,
+        }
+    }
+    #[inline(always)]
+    pub const fn with_aliases(mut self, aliases: &'static [&'static str]) -> Self {
+        self.aliases = aliases;
+        self
+    }
+    #[inline(always)]
+    pub const fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
+    }
+    #[inline]
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+}
+
+/// Declare an enum of unit variants implementing [ValueEnum] and
+/// [FromArgValue](crate::FromArgValue).
+///
+/// Every variant is followed by its name, any aliases, and optionally a help message:
+///
+/// ```ignore
+/// value_enum! {
+///     #[derive(Debug, Clone, Copy)]
+///     pub enum Format {
+///         Json = "json" | "js": "Compact JSON",
+///         Yaml = "yaml" | "yml",
+///         Toml = "toml",
+///     }
+/// }
+/// ```
+///
+/// `value_enum!(impl Type)` only implements [FromArgValue](crate::FromArgValue), for types
+/// implementing [ValueEnum] by hand.
+#[macro_export]
+macro_rules! value_enum {
+    (impl $ty:ty) => {
+        impl<'s> $crate::FromArgValue<'s> for $ty {
+            const POSSIBLE_VALUES: ::core::option::Option<&'static [$crate::PossibleValue]> =
+                Some(<$ty as $crate::ValueEnum>::POSSIBLE_VALUES);
+
+            #[inline]
+            fn from_arg_value(
+                ctx: &$crate::ArgContext,
+                value: &'s ::std::ffi::OsStr,
+                source: &$crate::ArgSource<'_, 's>,
+            ) -> ::core::result::Result<Self, $crate::ArgError<'s>> {
+                source
+                    .as_str(value)
+                    .and_then(<$ty as $crate::ValueEnum>::from_name)
+                    .ok_or_else(|| {
+                        let values = <$ty as $crate::ValueEnum>::POSSIBLE_VALUES;
+                        $crate::ArgError::InvalidChoice(::std::boxed::Box::new(*ctx), value, values)
+                    })
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:literal $(| $alias:literal)* $(: $help:literal)?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant),*
+        }
+
+        impl $crate::ValueEnum for $name {
+            const POSSIBLE_VALUES: &'static [$crate::PossibleValue] = &[$(
+                $crate::PossibleValue {
+                    name: $value,
+                    aliases: &[$($alias),*],
+                    help: $crate::value_enum!(@help $($help)?),
+                }
+            ),*];
+
+            #[inline]
+            fn from_index(index: usize) -> Self {
+                [$(Self::$variant),*]
+                    .into_iter()
+                    .nth(index)
+                    .expect("Index of a possible value")
+            }
+        }
+
+        $crate::value_enum!(impl $name);
+    };
+    (@help) => { None };
+    (@help $help:literal) => { Some($help) };
+}
@@ -1,30 +1,90 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    hash::Hash,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    num::{
+        NonZeroI8, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI128, NonZeroIsize, NonZeroU8,
+        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU128, NonZeroUsize,
+    },
+    ops::{Bound, RangeBounds},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use crate::{ArgError, ArgumentValue, arg::ArgContext, source::ArgSource};
+use crate::{
+    ArgError, ArgumentValue, FromArgValue, NegatableValue, PossibleValue, SingleValue,
+    arg::{ArgContext, Arity},
+    source::{ArgSource, split_os, split_os_once},
+};
 
 impl<'s> ArgumentValue<'s> for bool {
+    type Output = Self;
+    const FLAG: bool = true;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
     #[inline(always)]
     fn capture(
         &mut self,
-        _ctx: &ArgContext,
-        _args: &mut ArgSource<'_, 's>,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
     ) -> Result<(), ArgError<'s>> {
-        *self = true;
+        *self = match args.supplied_value() {
+            Some(value) => parse_supplied(ctx, value)?,
+            None => !args.is_negated(),
+        };
         Ok(())
     }
 }
+impl NegatableValue<'_> for bool {}
 impl<'s> ArgumentValue<'s> for u32 {
+    type Output = Self;
+    const FLAG: bool = true;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
     #[inline(always)]
     fn capture(
         &mut self,
-        _ctx: &ArgContext,
-        _args: &mut ArgSource<'_, 's>,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
     ) -> Result<(), ArgError<'s>> {
-        *self += 1;
+        *self = match args.supplied_value() {
+            Some(value) => parse_supplied(ctx, value)?,
+            None => *self + 1,
+        };
         Ok(())
     }
 }
-impl<'s> ArgumentValue<'s> for Option<&'s str> {
+/// Parse the value supplied for a flag or count, both accepting `true`, `false`, `1` and `0`.
+#[cold]
+fn parse_supplied<'s, T: FromArgValue<'s> + From<bool>>(
+    ctx: &ArgContext,
+    value: &'s OsStr,
+) -> Result<T, ArgError<'s>> {
+    match value.as_encoded_bytes() {
+        b"true" | b"1" => Ok(T::from(true)),
+        b"false" | b"0" => Ok(T::from(false)),
+        // Not the source being parsed, which would force it out of registers on the hot path
+        _ => T::from_arg_value(ctx, value, &ArgSource::new_os(&[])),
+    }
+}
+
+impl<'s, T: FromArgValue<'s>> ArgumentValue<'s> for Option<T> {
+    type Output = Self;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
     #[inline(always)]
     fn capture(
         &mut self,
@@ -32,20 +92,379 @@ impl<'s> ArgumentValue<'s> for Option<&'s str> {
         args: &mut ArgSource<'_, 's>,
     ) -> Result<(), ArgError<'s>> {
         let value = args
-            .next_value()
-            .ok_or(ArgError::MissingValueForOpt(*ctx))?;
-        *self = Some(value);
+            .next_value(ctx)
+            .ok_or_else(|| ArgError::MissingValueForOpt(Box::new(*ctx)))?;
+        *self = Some(T::from_arg_value(ctx, value, args)?);
+        Ok(())
+    }
+}
+impl<'s, T: FromArgValue<'s>> SingleValue<'s> for Option<T> {
+    type Value = T;
+
+    #[inline(always)]
+    fn parse_value(
+        &self,
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<T, ArgError<'s>> {
+        T::from_arg_value(ctx, value, source)
+    }
+    #[inline(always)]
+    fn into_value(self) -> Option<T> {
+        self
+    }
+}
+
+impl<'s> FromArgValue<'s> for &'s str {
+    #[inline(always)]
+    fn from_arg_value(
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        source
+            .as_str(value)
+            .ok_or_else(|| ArgError::InvalidValueForOpt(Box::new(*ctx), value, None))
+    }
+}
+impl<'s> FromArgValue<'s> for &'s OsStr {
+    #[inline(always)]
+    fn from_arg_value(
+        _ctx: &ArgContext,
+        value: &'s OsStr,
+        _source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        Ok(value)
+    }
+}
+impl<'s> FromArgValue<'s> for &'s Path {
+    #[inline(always)]
+    fn from_arg_value(
+        _ctx: &ArgContext,
+        value: &'s OsStr,
+        _source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        Ok(Path::new(value))
+    }
+}
+impl<'s> FromArgValue<'s> for OsString {
+    #[inline(always)]
+    fn from_arg_value(
+        _ctx: &ArgContext,
+        value: &'s OsStr,
+        _source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        Ok(value.to_owned())
+    }
+}
+impl<'s> FromArgValue<'s> for PathBuf {
+    #[inline(always)]
+    fn from_arg_value(
+        _ctx: &ArgContext,
+        value: &'s OsStr,
+        _source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        Ok(PathBuf::from(value))
+    }
+}
+
+/// Implement [FromArgValue](crate::FromArgValue) for types implementing
+/// [FromStr](std::str::FromStr), such as `from_str_values!(Color, Level)`.
+///
+/// This lets them be used in `Option<T>`, `Vec<T>` and every other argument built on
+/// [FromArgValue](crate::FromArgValue). Types of other crates can't be passed to it, wrap them in
+/// [FromStrValue](crate::FromStrValue) instead.
+#[macro_export]
+macro_rules! from_str_values {
+    ($($ty:ty),+ $(,)?) => {$(
+        impl<'s> $crate::FromArgValue<'s> for $ty {
+            #[inline(always)]
+            fn from_arg_value(
+                ctx: &$crate::ArgContext,
+                value: &'s ::std::ffi::OsStr,
+                source: &$crate::ArgSource<'_, 's>,
+            ) -> ::core::result::Result<Self, $crate::ArgError<'s>> {
+                source
+                    .as_str(value)
+                    .ok_or_else(|| {
+                        $crate::ArgError::InvalidValueForOpt(::std::boxed::Box::new(*ctx), value, None)
+                    })?
+                    .parse()
+                    .map_err(|err| $crate::ArgError::invalid_value(ctx, value, err))
+            }
+        }
+    )+};
+}
+
+from_str_values!(
+    String,
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroU128,
+    NonZeroUsize,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    NonZeroI128,
+    NonZeroIsize,
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+);
+
+/// Take exactly `N` values for one occurrence of an option.
+#[inline(always)]
+fn take_exactly<'s, const N: usize>(
+    ctx: &ArgContext,
+    args: &mut ArgSource<'_, 's>,
+) -> Result<[&'s OsStr; N], ArgError<'s>> {
+    let mut values = [OsStr::new(""); N];
+    for (found, value) in values.iter_mut().enumerate() {
+        *value = args.next_value(ctx).ok_or_else(|| {
+            ArgError::WrongNumberOfValues(Box::new(*ctx), Arity::exactly(N), found)
+        })?;
+    }
+    Ok(values)
+}
+
+impl<'s, T: FromArgValue<'s>, const N: usize> ArgumentValue<'s> for Option<[T; N]> {
+    type Output = Self;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
+    #[inline]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        let values = take_exactly::<N>(ctx, args)?;
+        let parsed = values
+            .into_iter()
+            .map(|value| T::from_arg_value(ctx, value, args))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Ok(parsed) = parsed.try_into() else {
+            unreachable!()
+        };
+        *self = Some(parsed);
+        Ok(())
+    }
+}
+
+/// Implement [ArgumentValue] for options taking a tuple of values, such as `--point 1 2`.
+macro_rules! tuple_values {
+    ($(($($ty:ident $value:ident),+)),+ $(,)?) => {$(
+        impl<'s, $($ty: FromArgValue<'s>),+> ArgumentValue<'s> for Option<($($ty,)+)> {
+            type Output = Self;
+
+            #[inline(always)]
+            fn into_output(self) -> Self {
+                self
+            }
+            #[inline]
+            fn capture(
+                &mut self,
+                ctx: &ArgContext,
+                args: &mut ArgSource<'_, 's>,
+            ) -> Result<(), ArgError<'s>> {
+                let [$($value),+] = take_exactly(ctx, args)?;
+                *self = Some(($($ty::from_arg_value(ctx, $value, args)?,)+));
+                Ok(())
+            }
+        }
+    )+};
+}
+
+tuple_values!(
+    (A a),
+    (A a, B b),
+    (A a, B b, C c),
+    (A a, B b, C c, D d),
+);
+
+/// Take the values of one occurrence of an option, splitting each on the delimiter of `ctx`.
+///
+/// Takes `arity` values unless `ctx` sets its own.
+#[inline]
+fn for_each_value<'s>(
+    ctx: &ArgContext,
+    args: &mut ArgSource<'_, 's>,
+    arity: Arity,
+    mut each: impl FnMut(&'s OsStr, &ArgSource<'_, 's>) -> Result<(), ArgError<'s>>,
+) -> Result<(), ArgError<'s>> {
+    let arity = ctx.arity.unwrap_or(arity);
+    let mut found = 0;
+    while found < arity.max
+        && let Some(value) = args.next_value(ctx)
+    {
+        found += 1;
+        match ctx.delimiter {
+            Some(delimiter) => {
+                for value in split_os(value, delimiter as u8) {
+                    each(value, args)?;
+                }
+            }
+            None => each(value, args)?,
+        }
+    }
+    match found {
+        found if found >= arity.min => Ok(()),
+        0 if ctx.arity.is_none() => Err(ArgError::MissingValueForOpt(Box::new(*ctx))),
+        found => Err(ArgError::WrongNumberOfValues(Box::new(*ctx), arity, found)),
+    }
+}
+
+impl<'s, T: FromArgValue<'s>> ArgumentValue<'s> for Option<Vec<T>> {
+    type Output = Self;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
+    #[inline]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        let mut values = Vec::new();
+        for_each_value(ctx, args, Arity::GREEDY, |value, args| {
+            values.push(T::from_arg_value(ctx, value, args)?);
+            Ok(())
+        })?;
+        *self = Some(values);
         Ok(())
     }
 }
 
+/// Implement [ArgumentValue] for collections adding to themselves every time an option is given,
+/// such as `-I dir -I dir2`.
+macro_rules! collection_values {
+    ($(($collection:ident, $insert:ident $(, $bound:path)*)),+ $(,)?) => {$(
+        impl<'s, T: FromArgValue<'s> $(+ $bound)*> ArgumentValue<'s> for $collection<T> {
+            type Output = Self;
+            const MULTIPLE: bool = true;
+            const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+            #[inline(always)]
+            fn into_output(self) -> Self {
+                self
+            }
+            #[inline]
+            fn capture(
+                &mut self,
+                ctx: &ArgContext,
+                args: &mut ArgSource<'_, 's>,
+            ) -> Result<(), ArgError<'s>> {
+                for_each_value(ctx, args, Arity::exactly(1), |value, args| {
+                    self.$insert(T::from_arg_value(ctx, value, args)?);
+                    Ok(())
+                })
+            }
+        }
+    )+};
+}
+
+collection_values!(
+    (Vec, push),
+    (HashSet, insert, Eq, Hash),
+    (BTreeSet, insert, Ord),
+);
+
+/// Collects `key=value` pairs, such as `-D name=value`.
+impl<'s, K: FromArgValue<'s> + Ord, V: FromArgValue<'s>> ArgumentValue<'s> for BTreeMap<K, V> {
+    type Output = Self;
+    const MULTIPLE: bool = true;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
+    #[inline]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        for_each_value(ctx, args, Arity::exactly(1), |pair, args| {
+            let (key, value) = split_os_once(pair, b'=')
+                .ok_or_else(|| ArgError::MalformedPair(Box::new(*ctx), pair))?;
+            let key = K::from_arg_value(ctx, key, args)?;
+            self.insert(key, V::from_arg_value(ctx, value, args)?);
+            Ok(())
+        })
+    }
+}
+
+/// A value parsed with [FromStr], for any type in `Option<T>`, `Vec<T>` and every other argument
+/// built on [FromArgValue], such as `Vec<FromStrValue<Url>>`.
+///
+/// Types of this crate can also implement [FromArgValue] with [crate::from_str_values].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FromStrValue<T>(pub T);
+
+impl<'s, T: FromStr> FromArgValue<'s> for FromStrValue<T>
+where
+    T::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+    #[inline(always)]
+    fn from_arg_value(
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<Self, ArgError<'s>> {
+        source
+            .as_str(value)
+            .ok_or_else(|| ArgError::InvalidValueForOpt(Box::new(*ctx), value, None))?
+            .parse()
+            .map(FromStrValue)
+            .map_err(|err| ArgError::invalid_value(ctx, value, err))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum OptFromStrWrapper<T: FromStr> {
     NotFound,
     Found(T),
 }
 
-impl<'s, T: FromStr> ArgumentValue<'s> for OptFromStrWrapper<T> {
+impl<'s, T: FromStr> ArgumentValue<'s> for OptFromStrWrapper<T>
+where
+    T::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Output = Self;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
     #[inline(always)]
     fn capture(
         &mut self,
@@ -53,13 +472,229 @@ impl<'s, T: FromStr> ArgumentValue<'s> for OptFromStrWrapper<T> {
         args: &mut ArgSource<'_, 's>,
     ) -> Result<(), ArgError<'s>> {
         let value = args
-            .next_value()
-            .ok_or(ArgError::MissingValueForOpt(*ctx))?;
-        let parsed = value
+            .next_value(ctx)
+            .ok_or_else(|| ArgError::MissingValueForOpt(Box::new(*ctx)))?;
+        *self = OptFromStrWrapper::Found(self.parse_value(ctx, value, args)?);
+        Ok(())
+    }
+}
+
+impl<'s, T: FromStr> SingleValue<'s> for OptFromStrWrapper<T>
+where
+    T::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Value = T;
+
+    #[inline(always)]
+    fn parse_value(
+        &self,
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<T, ArgError<'s>> {
+        source
+            .as_str(value)
+            .ok_or_else(|| ArgError::InvalidValueForOpt(Box::new(*ctx), value, None))?
             .parse()
-            .ok()
-            .ok_or(ArgError::InvalidValueForOpt(*ctx, value))?;
-        *self = OptFromStrWrapper::Found(parsed);
+            .map_err(|err| ArgError::invalid_value(ctx, value, err))
+    }
+    #[inline(always)]
+    fn into_value(self) -> Option<T> {
+        match self {
+            OptFromStrWrapper::NotFound => None,
+            OptFromStrWrapper::Found(value) => Some(value),
+        }
+    }
+}
+
+/// An option falling back to a default value, see [crate::Arg::with_default].
+#[derive(Debug, Clone, Copy)]
+pub struct Defaulted<'s, T: SingleValue<'s>> {
+    pub value: T,
+    pub default: T::Value,
+}
+
+impl<'s, T: SingleValue<'s>> ArgumentValue<'s> for Defaulted<'s, T> {
+    type Output = T::Value;
+    const MULTIPLE: bool = T::MULTIPLE;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> T::Value {
+        self.value.into_value().unwrap_or(self.default)
+    }
+    #[inline(always)]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        self.value.capture(ctx, args)
+    }
+}
+
+/// A single value checked or transformed by `map` as it's captured, see [crate::Arg::try_map].
+pub struct TryMap<T, U, F> {
+    /// Parses values before they're mapped, without holding any.
+    pub(crate) inner: T,
+    pub value: Option<U>,
+    pub(crate) map: F,
+}
+
+impl<'s, T, U, F> ArgumentValue<'s> for TryMap<T, U, F>
+where
+    T: SingleValue<'s>,
+    F: MapValue<T::Value, U>,
+{
+    type Output = Option<U>;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> Option<U> {
+        self.value
+    }
+    #[inline(always)]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        let value = args
+            .next_value(ctx)
+            .ok_or_else(|| ArgError::MissingValueForOpt(Box::new(*ctx)))?;
+        self.value = Some(self.parse_value(ctx, value, args)?);
+        Ok(())
+    }
+}
+
+impl<'s, T, U, F> SingleValue<'s> for TryMap<T, U, F>
+where
+    T: SingleValue<'s>,
+    F: MapValue<T::Value, U>,
+{
+    type Value = U;
+
+    #[inline(always)]
+    fn parse_value(
+        &self,
+        ctx: &ArgContext,
+        value: &'s OsStr,
+        source: &ArgSource<'_, 's>,
+    ) -> Result<U, ArgError<'s>> {
+        let parsed = self.inner.parse_value(ctx, value, source)?;
+        (self.map)(parsed)
+            .map_err(|message| ArgError::ValidationFailed(Box::new(*ctx), value, message))
+    }
+    #[inline(always)]
+    fn into_value(self) -> Option<U> {
+        self.value
+    }
+}
+
+/// Maps a parsed value, returning the reason it's rejected on errors, see [TryMap].
+pub trait MapValue<V, U>: Fn(V) -> Result<U, String> {}
+
+impl<V, U, F: Fn(V) -> Result<U, String>> MapValue<V, U> for F {}
+
+/// A [TryMap] returning values unchanged once they're checked, see [crate::Arg::validate].
+pub type Validated<'s, T, F> = TryMap<T, <T as SingleValue<'s>>::Value, F>;
+
+/// Describe the values in `range`, such as `at least 1 and at most 65535`.
+pub(crate) fn describe_range<T: Display>(range: &impl RangeBounds<T>) -> String {
+    let start = match range.start_bound() {
+        Bound::Included(start) => Some(format!("at least {start}")),
+        Bound::Excluded(start) => Some(format!("greater than {start}")),
+        Bound::Unbounded => None,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(end) => Some(format!("at most {end}")),
+        Bound::Excluded(end) => Some(format!("less than {end}")),
+        Bound::Unbounded => None,
+    };
+    match (start, end) {
+        (Some(start), Some(end)) => format!("{start} and {end}"),
+        (Some(bound), None) | (None, Some(bound)) => bound,
+        (None, None) => "anything".to_owned(),
+    }
+}
+
+/// A value that must be given, such as a positional argument that can't be left out.
+///
+/// Parsing fails with [ArgError::MissingPositional] if it isn't given, which makes
+/// [crate::Arguments::into_values] panic if called after a failed parse.
+#[derive(Debug, Clone, Copy)]
+pub struct Required<T>(pub Option<T>);
+
+impl<T> Default for Required<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<'s, T: FromArgValue<'s>> ArgumentValue<'s> for Required<T> {
+    type Output = T;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+    const REQUIRED: bool = true;
+
+    #[inline(always)]
+    fn into_output(self) -> T {
+        self.0.expect("Required argument was not given")
+    }
+    #[inline(always)]
+    fn is_missing(&self) -> bool {
+        self.0.is_none()
+    }
+    #[inline(always)]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        self.0.capture(ctx, args)
+    }
+}
+
+/// An option whose value is optional, such as `--color` and `--color=always`.
+///
+/// Only attached values are read, so `--color always` leaves `always` as a positional value.
+#[derive(Debug, Clone, Copy)]
+pub struct OptionalValue<T> {
+    /// Used when the option is given without a value.
+    pub default: T,
+    /// Set once the option is given.
+    pub value: Option<T>,
+}
+
+impl<T> OptionalValue<T> {
+    #[inline(always)]
+    pub fn new(default: T) -> Self {
+        Self {
+            default,
+            value: None,
+        }
+    }
+}
+
+impl<'s, T: FromArgValue<'s> + Clone> ArgumentValue<'s> for OptionalValue<T> {
+    type Output = Self;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
+    #[inline(always)]
+    fn capture(
+        &mut self,
+        ctx: &ArgContext,
+        args: &mut ArgSource<'_, 's>,
+    ) -> Result<(), ArgError<'s>> {
+        let Some(value) = args.attached_value() else {
+            self.value = Some(self.default.clone());
+            return Ok(());
+        };
+        self.value = Some(T::from_arg_value(ctx, value, args)?);
         Ok(())
     }
 }
@@ -68,6 +703,20 @@ impl<'s, T: FromStr> ArgumentValue<'s> for OptFromStrWrapper<T> {
 pub struct SetViaRef<'m, T>(pub &'m mut T);
 
 impl<'m, 's, T: ArgumentValue<'s>> ArgumentValue<'s> for SetViaRef<'m, T> {
+    type Output = Self;
+    const MULTIPLE: bool = T::MULTIPLE;
+    const POSSIBLE_VALUES: Option<&'static [PossibleValue]> = T::POSSIBLE_VALUES;
+    const REQUIRED: bool = T::REQUIRED;
+    const FLAG: bool = T::FLAG;
+
+    #[inline(always)]
+    fn into_output(self) -> Self {
+        self
+    }
+    #[inline(always)]
+    fn is_missing(&self) -> bool {
+        self.0.is_missing()
+    }
     #[inline(always)]
     fn capture(
         &mut self,
@@ -77,3 +726,4 @@ impl<'m, 's, T: ArgumentValue<'s>> ArgumentValue<'s> for SetViaRef<'m, T> {
         self.0.capture(ctx, args)
     }
 }
+impl<'m, 's, T: NegatableValue<'s>> NegatableValue<'s> for SetViaRef<'m, T> {}